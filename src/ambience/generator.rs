@@ -0,0 +1,211 @@
+use std::{error::Error, num::NonZeroU32};
+
+use rltk::{FastNoise, FractalType, NoiseType, Point, RandomNumberGenerator};
+use serde::{Deserialize, Serialize};
+
+use crate::arg_value;
+
+use super::map::{MAP_BORDER, MAP_DIMENSION, MapField, MapTile, empty_map, map_tile, set_map_tile};
+
+pub trait MapGenerator {
+    fn generate(&self) -> MapField;
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum MapPreset {
+    Scatter,
+    Canal,
+    #[default]
+    Noise,
+}
+
+/// Canals cut the map every 10 tiles
+const CANAL_STEP: NonZeroU32 = NonZeroU32::new(10).unwrap();
+
+impl MapPreset {
+    /// Preset of the `--map <scatter|canal|noise>` command-line flag, noise when it is missing
+    pub fn from_args() -> Result<Self, Box<dyn Error + Send + Sync>> {
+        match arg_value("--map").as_deref() {
+            None => Ok(MapPreset::default()),
+            Some("scatter") => Ok(MapPreset::Scatter),
+            Some("canal") => Ok(MapPreset::Canal),
+            Some("noise") => Ok(MapPreset::Noise),
+            Some(other) => {
+                Err(format!("--map expects scatter, canal or noise, got {other}").into())
+            }
+        }
+    }
+    pub fn generator(&self, seed: u64) -> Box<dyn MapGenerator> {
+        match self {
+            MapPreset::Scatter => Box::new(ScatterGenerator::new(seed)),
+            MapPreset::Canal => Box::new(CanalGenerator { step: CANAL_STEP }),
+            MapPreset::Noise => Box::new(NoiseGenerator::new(seed)),
+        }
+    }
+}
+
+/// Grass and water scattered cell by cell
+pub struct ScatterGenerator {
+    pub seed: u64,
+    pub grass_density: f32,
+    pub water_density: f32,
+}
+
+impl ScatterGenerator {
+    pub fn new(seed: u64) -> Self {
+        ScatterGenerator {
+            seed,
+            grass_density: 0.75,
+            water_density: 0.1,
+        }
+    }
+}
+
+impl MapGenerator for ScatterGenerator {
+    fn generate(&self) -> MapField {
+        let mut map = empty_map();
+        let mut rng = RandomNumberGenerator::seeded(self.seed);
+        let area = MAP_DIMENSION.0 as f32 * MAP_DIMENSION.1 as f32;
+
+        for _i in 0..(area * self.grass_density) as usize {
+            let x = rng.range(0, MAP_DIMENSION.0);
+            let y = rng.range(0, MAP_DIMENSION.1);
+            map[x][y] = MapTile::Grass;
+        }
+        for _i in 0..(area * self.water_density) as usize {
+            let x = rng.range(0, MAP_DIMENSION.0);
+            let y = rng.range(0, MAP_DIMENSION.1);
+            map[x][y] = MapTile::Water;
+        }
+        map
+    }
+}
+
+/// Ground cut into square plots by water canals
+pub struct CanalGenerator {
+    pub step: NonZeroU32,
+}
+
+impl MapGenerator for CanalGenerator {
+    fn generate(&self) -> MapField {
+        let mut map = empty_map();
+        let step = self.step.get() as i32;
+        MAP_BORDER.for_each(|p| {
+            if p.x % step == 0 || p.y % step == 0 {
+                set_map_tile(&mut map, p, MapTile::Water);
+            }
+        });
+        map
+    }
+}
+
+pub struct NoiseParams {
+    pub elevation_frequency: f32,
+    pub vegetation_frequency: f32,
+    pub octaves: i32,
+    /// Elevation below the level is flooded into lakes
+    pub water_level: f32,
+    /// Vegetation above the level grows into forest
+    pub forest_level: f32,
+    /// Vegetation above the level grows into meadow grass
+    pub meadow_level: f32,
+    pub rivers: usize,
+    pub river_width: i32,
+}
+
+impl Default for NoiseParams {
+    fn default() -> Self {
+        NoiseParams {
+            elevation_frequency: 0.02,
+            vegetation_frequency: 0.05,
+            octaves: 4,
+            water_level: -0.35,
+            forest_level: 0.3,
+            meadow_level: -0.1,
+            rivers: 2,
+            river_width: 2,
+        }
+    }
+}
+
+/// Fractal Perlin noise landscape: lakes in the lowlands, forests and meadows
+/// by a second vegetation noise, rivers carved from the north edge to the south one
+pub struct NoiseGenerator {
+    pub seed: u64,
+    pub params: NoiseParams,
+}
+
+impl NoiseGenerator {
+    pub fn new(seed: u64) -> Self {
+        NoiseGenerator {
+            seed,
+            params: NoiseParams::default(),
+        }
+    }
+
+    fn noise(&self, seed: u64, frequency: f32) -> FastNoise {
+        let mut noise = FastNoise::seeded(seed);
+        noise.set_noise_type(NoiseType::PerlinFractal);
+        noise.set_fractal_type(FractalType::FBM);
+        noise.set_fractal_octaves(self.params.octaves);
+        noise.set_fractal_gain(0.5);
+        noise.set_fractal_lacunarity(2.0);
+        noise.set_frequency(frequency);
+        noise
+    }
+
+    fn carve_river(
+        &self,
+        map: &mut MapField,
+        elevation: &FastNoise,
+        rng: &mut RandomNumberGenerator,
+    ) {
+        let mut p = Point::new(rng.range(MAP_BORDER.x1, MAP_BORDER.x2), MAP_BORDER.y1);
+        while map_tile(map, p).is_some() {
+            for dx in 0..self.params.river_width {
+                set_map_tile(map, Point::new(p.x + dx, p.y), MapTile::Water);
+            }
+            // The river flows south and meanders toward the lower neighbour
+            let mut height =
+                |x: i32| elevation.get_noise(x as f32, (p.y + 1) as f32) + rng.rand::<f32>() * 0.2;
+            let (left, straight, right) = (height(p.x - 1), height(p.x), height(p.x + 1));
+            if left < straight && left < right {
+                p.x -= 1;
+            } else if right < straight {
+                p.x += 1;
+            }
+            p.y += 1;
+        }
+    }
+}
+
+impl MapGenerator for NoiseGenerator {
+    fn generate(&self) -> MapField {
+        let mut map = empty_map();
+        let elevation = self.noise(self.seed, self.params.elevation_frequency);
+        let vegetation = self.noise(self.seed.wrapping_add(1), self.params.vegetation_frequency);
+
+        MAP_BORDER.for_each(|p| {
+            let (x, y) = (p.x as f32, p.y as f32);
+            let tile = if elevation.get_noise(x, y) < self.params.water_level {
+                MapTile::Water
+            } else {
+                let v = vegetation.get_noise(x, y);
+                if v > self.params.forest_level {
+                    MapTile::Forest
+                } else if v > self.params.meadow_level {
+                    MapTile::Grass
+                } else {
+                    MapTile::Ground
+                }
+            };
+            set_map_tile(&mut map, p, tile);
+        });
+
+        let mut rng = RandomNumberGenerator::seeded(self.seed);
+        for _i in 0..self.params.rivers {
+            self.carve_river(&mut map, &elevation, &mut rng);
+        }
+        map
+    }
+}
@@ -1,4 +1,6 @@
-use rltk::Rect;
+use rltk::{Point, Rect};
+use serde::{Deserialize, Serialize};
+
 pub const MAP_BORDER: Rect = Rect {
     x1: -100,
     y1: -100,
@@ -7,43 +9,145 @@ pub const MAP_BORDER: Rect = Rect {
 };
 
 //TODO make Point to_tuple as const
-const MAP_DIMENSION: (usize, usize) = (
+pub const MAP_DIMENSION: (usize, usize) = (
     (MAP_BORDER.x2 - MAP_BORDER.x1) as usize,
     (MAP_BORDER.y2 - MAP_BORDER.y1) as usize,
 );
 
 pub type MapField = [[MapTile; MAP_DIMENSION.1 as usize]; MAP_DIMENSION.0 as usize];
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum MapTile {
     Ground,
     Water,
     Grass,
+    Forest,
+    /// Frozen water, walkable until the spring
+    Ice,
+    /// Built by the workers, nothing walks through
+    Fence,
+}
+
+pub struct TileProperties {
+    pub walkable: bool,
+    /// Multiplier of the time needed to step onto the tile
+    pub move_cost: f32,
+    pub blocks_sight: bool,
+    pub fertile: bool,
 }
 
-pub fn random_map() -> MapField {
-    let mut map = [[MapTile::Ground; MAP_DIMENSION.1 as usize]; MAP_DIMENSION.0 as usize];
-    let mut rng = rltk::RandomNumberGenerator::new();
+impl MapTile {
+    pub const fn properties(&self) -> TileProperties {
+        match self {
+            MapTile::Ground => TileProperties {
+                walkable: true,
+                move_cost: 1.,
+                blocks_sight: false,
+                fertile: true,
+            },
+            MapTile::Water => TileProperties {
+                walkable: false,
+                move_cost: 1.,
+                blocks_sight: false,
+                fertile: false,
+            },
+            MapTile::Grass => TileProperties {
+                walkable: true,
+                move_cost: 1.25,
+                blocks_sight: false,
+                fertile: true,
+            },
+            MapTile::Forest => TileProperties {
+                walkable: true,
+                move_cost: 2.,
+                blocks_sight: true,
+                fertile: false,
+            },
+            MapTile::Ice => TileProperties {
+                walkable: true,
+                move_cost: 1.5,
+                blocks_sight: false,
+                fertile: false,
+            },
+            MapTile::Fence => TileProperties {
+                walkable: false,
+                move_cost: 1.,
+                blocks_sight: false,
+                fertile: false,
+            },
+        }
+    }
+}
 
-    for _i in 0..(MAP_DIMENSION.0 as f32 * MAP_DIMENSION.1 as f32 * 0.75) as usize {
-        let x = rng.range(0, MAP_DIMENSION.0);
-        let y = rng.range(0, MAP_DIMENSION.1);
-        map[x][y] = MapTile::Grass;
+pub fn map_tile(map: &MapField, p: Point) -> Option<MapTile> {
+    if !MAP_BORDER.point_in_rect(p) {
+        return None;
     }
-    for _i in 0..(MAP_DIMENSION.0 as f32 * MAP_DIMENSION.1 as f32 * 0.1) as usize {
-        let x = rng.range(0, MAP_DIMENSION.0);
-        let y = rng.range(0, MAP_DIMENSION.1);
-        map[x][y] = MapTile::Water;
+    Some(map[(p.x - MAP_BORDER.x1) as usize][(p.y - MAP_BORDER.y1) as usize])
+}
+
+/// Index of `p` in the flattened `MapField`, for the layers kept along the map
+pub fn tile_index(p: Point) -> Option<usize> {
+    if !MAP_BORDER.point_in_rect(p) {
+        return None;
     }
-    map
+    let (x, y) = (
+        (p.x - MAP_BORDER.x1) as usize,
+        (p.y - MAP_BORDER.y1) as usize,
+    );
+    Some(x * MAP_DIMENSION.1 + y)
 }
 
-pub fn _canal_map() -> MapField {
-    let mut map = [[MapTile::Ground; MAP_DIMENSION.1 as usize]; MAP_DIMENSION.0 as usize];
-    MAP_BORDER.for_each(|p| {
-        if p.x % 10 == 0 || p.y % 10 == 0 {
-            map[(p.x - MAP_BORDER.x1) as usize][(p.y - MAP_BORDER.y1) as usize] = MapTile::Water;
+/// Counts the changes of the map since it was generated or loaded, caches derived from
+/// the map keep the revision they were computed at to find out they are stale
+#[derive(Default)]
+pub struct MapRevision(u64);
+
+impl MapRevision {
+    pub fn get(&self) -> u64 {
+        self.0
+    }
+    pub fn bump(&mut self) {
+        self.0 += 1;
+    }
+}
+
+pub fn set_map_tile(map: &mut MapField, p: Point, tile: MapTile) {
+    if MAP_BORDER.point_in_rect(p) {
+        map[(p.x - MAP_BORDER.x1) as usize][(p.y - MAP_BORDER.y1) as usize] = tile;
+    }
+}
+
+/// Turns all the water into ice or the ice back into water
+pub fn set_water_frozen(map: &mut MapField, is_frozen: bool) {
+    let (from, to) = if is_frozen {
+        (MapTile::Water, MapTile::Ice)
+    } else {
+        (MapTile::Ice, MapTile::Water)
+    };
+    for tile in map.iter_mut().flatten() {
+        if *tile == from {
+            *tile = to;
         }
-    });
-    map
+    }
+}
+
+pub fn is_walkable(map: &MapField, p: Point) -> bool {
+    map_tile(map, p).is_some_and(|t| t.properties().walkable)
+}
+
+pub fn empty_map() -> MapField {
+    [[MapTile::Ground; MAP_DIMENSION.1 as usize]; MAP_DIMENSION.0 as usize]
+}
+
+/// Closest walkable tile searched in growing squares around `p`
+pub fn nearest_walkable(map: &MapField, p: Point) -> Option<Point> {
+    let radius = MAP_DIMENSION.0.max(MAP_DIMENSION.1) as i32;
+    (0..radius).find_map(|r| {
+        Rect::with_exact(p.x - r, p.y - r, p.x + r + 1, p.y + r + 1)
+            .point_set()
+            .into_iter()
+            .filter(|c| is_walkable(map, *c))
+            .min_by_key(|c| ((c.x - p.x).abs() + (c.y - p.y).abs(), c.x, c.y))
+    })
 }
@@ -1 +1,4 @@
+pub mod generator;
 pub mod map;
+pub mod path;
+pub mod soil;
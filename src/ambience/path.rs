@@ -0,0 +1,167 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+};
+
+use rltk::Point;
+
+use super::map::{MapField, is_walkable, map_tile};
+
+const NEIGHBOURS: [Point; 4] = [
+    Point::constant(-1, 0),
+    Point::constant(1, 0),
+    Point::constant(0, -1),
+    Point::constant(0, 1),
+];
+
+#[derive(PartialEq, Eq)]
+struct Node {
+    estimate: i32,
+    cost: i32,
+    point: Point,
+}
+
+// BinaryHeap is a max-heap, so the order is reversed to pop the cheapest estimate first
+impl Ord for Node {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .estimate
+            .cmp(&self.estimate)
+            .then_with(|| self.cost.cmp(&other.cost))
+    }
+}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Costs are kept integral for the heap, the cheapest tile costs COST_SCALE
+const COST_SCALE: f32 = 100.;
+
+fn step_cost(map: &MapField, p: Point) -> i32 {
+    map_tile(map, p).map_or(0, |t| (t.properties().move_cost * COST_SCALE) as i32)
+}
+
+fn distance(a: Point, b: Point) -> i32 {
+    ((a.x - b.x).abs() + (a.y - b.y).abs()) * COST_SCALE as i32
+}
+
+/// A* search over the four directions `Mover` is able to step in.
+/// Returned path excludes `start` and ends with `goal`.
+pub fn find_path(map: &MapField, start: Point, goal: Point) -> Option<Vec<Point>> {
+    if start == goal {
+        return Some(Vec::new());
+    }
+    if !is_walkable(map, goal) {
+        return None;
+    }
+
+    let mut open = BinaryHeap::new();
+    let mut came_from: HashMap<Point, Point> = HashMap::new();
+    let mut costs: HashMap<Point, i32> = HashMap::new();
+    costs.insert(start, 0);
+    open.push(Node {
+        estimate: distance(start, goal),
+        cost: 0,
+        point: start,
+    });
+
+    while let Some(Node { cost, point, .. }) = open.pop() {
+        if point == goal {
+            return Some(restore_path(&came_from, start, goal));
+        }
+        if cost > costs[&point] {
+            continue;
+        }
+        for delta in NEIGHBOURS {
+            let next = point + delta;
+            if !is_walkable(map, next) {
+                continue;
+            }
+            let next_cost = cost + step_cost(map, next);
+            if costs.get(&next).is_some_and(|&c| c <= next_cost) {
+                continue;
+            }
+            costs.insert(next, next_cost);
+            came_from.insert(next, point);
+            open.push(Node {
+                estimate: next_cost + distance(next, goal),
+                cost: next_cost,
+                point: next,
+            });
+        }
+    }
+    None
+}
+
+fn restore_path(came_from: &HashMap<Point, Point>, start: Point, goal: Point) -> Vec<Point> {
+    let mut path = vec![goal];
+    let mut current = goal;
+    while let Some(&previous) = came_from.get(&current) {
+        if previous == start {
+            break;
+        }
+        path.push(previous);
+        current = previous;
+    }
+    path.reverse();
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU32;
+
+    use rltk::Point;
+
+    use super::find_path;
+    use crate::ambience::{
+        generator::{CanalGenerator, MapGenerator},
+        map::{MapField, MapTile, set_map_tile},
+    };
+
+    /// Plots of ground cut every 10 tiles by water canals
+    fn canal_map() -> MapField {
+        let step = NonZeroU32::new(10).unwrap();
+        CanalGenerator { step }.generate()
+    }
+
+    #[test]
+    fn crosses_canal_by_bridge() {
+        let mut map = canal_map();
+        let bridge = Point::new(10, 2);
+        set_map_tile(&mut map, bridge, MapTile::Ground);
+        let start = Point::new(5, 5);
+        let goal = Point::new(15, 5);
+
+        let path = find_path(&map, start, goal).expect("Path over the bridge");
+        assert_eq!(path.last(), Some(&goal));
+        assert!(path.contains(&bridge));
+        let mut previous = start;
+        for p in &path {
+            assert_eq!((p.x - previous.x).abs() + (p.y - previous.y).abs(), 1);
+            previous = *p;
+        }
+    }
+
+    #[test]
+    fn unreachable_plot() {
+        let map = canal_map();
+        assert_eq!(find_path(&map, Point::new(5, 5), Point::new(15, 5)), None);
+    }
+
+    #[test]
+    fn start_is_goal() {
+        let map = canal_map();
+        let p = Point::new(5, 5);
+        assert_eq!(find_path(&map, p, p), Some(Vec::new()));
+    }
+
+    #[test]
+    fn goal_not_walkable() {
+        let map = canal_map();
+        assert_eq!(find_path(&map, Point::new(5, 5), Point::new(10, 5)), None);
+    }
+}
@@ -0,0 +1,158 @@
+use edict::prelude::{Res, ResMut};
+use rltk::Point;
+use serde::{Deserialize, Serialize};
+
+use crate::{calendar::DAY_LENGTH, game_clock::GameClock, weather::Weather};
+
+use super::map::{MAP_DIMENSION, MapField, MapTile, tile_index};
+
+/// Game milliseconds between the soil updates, an hour of the day
+const UPDATE_TIME: f32 = DAY_LENGTH / 24.;
+/// Share of the difference with the neighbours evened out by an update
+const DIFFUSION: f32 = 0.25;
+/// Fertility taken by every harvest
+const HARVEST_DEPLETION: f32 = 0.15;
+/// Fertility coming back on its own by an update
+const FALLOW_RECOVERY: f32 = 0.002;
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct SoilTile {
+    /// From 0 for dry dust to 1 for mud
+    pub moisture: f32,
+    pub fertility: f32,
+    pub is_tilled: bool,
+}
+
+impl SoilTile {
+    fn new(tile: MapTile) -> Self {
+        let fertility = match tile {
+            MapTile::Forest => 1.,
+            MapTile::Grass => 0.8,
+            MapTile::Ground => 0.6,
+            MapTile::Water | MapTile::Ice | MapTile::Fence => 0.,
+        };
+        SoilTile {
+            moisture: if is_wet(tile) { 1. } else { 0.5 },
+            fertility,
+            is_tilled: false,
+        }
+    }
+    /// Multiplier of the crop growth speed, dry or waterlogged soil slows the crops down
+    pub fn growth_rate(&self) -> f32 {
+        let water = match self.moisture {
+            m if m < 0.2 => 0.5,
+            m if m > 0.9 => 0.75,
+            _ => 1.,
+        };
+        let tillage = if self.is_tilled { 1.25 } else { 1. };
+        (0.5 + self.fertility) * water * tillage
+    }
+}
+
+fn is_wet(tile: MapTile) -> bool {
+    matches!(tile, MapTile::Water | MapTile::Ice)
+}
+
+/// Soil under every map tile, in the order of the flattened `MapField`
+pub struct Soil {
+    tiles: Vec<SoilTile>,
+    /// Game time left until the next update
+    until_update: f32,
+}
+
+impl Soil {
+    pub fn new(map: &MapField) -> Self {
+        Soil {
+            tiles: map.iter().flatten().map(|t| SoilTile::new(*t)).collect(),
+            until_update: UPDATE_TIME,
+        }
+    }
+    pub fn get(&self, p: Point) -> Option<&SoilTile> {
+        tile_index(p).map(|i| &self.tiles[i])
+    }
+    fn get_mut(&mut self, p: Point) -> Option<&mut SoilTile> {
+        tile_index(p).map(|i| &mut self.tiles[i])
+    }
+    pub fn growth_rate(&self, p: Point) -> f32 {
+        self.get(p).map_or(1., |t| t.growth_rate())
+    }
+    pub fn till(&mut self, p: Point) {
+        if let Some(tile) = self.get_mut(p) {
+            tile.is_tilled = true;
+        }
+    }
+    pub fn water(&mut self, p: Point) {
+        if let Some(tile) = self.get_mut(p) {
+            tile.moisture = 1.;
+        }
+    }
+    pub fn deplete(&mut self, p: Point) {
+        if let Some(tile) = self.get_mut(p) {
+            tile.fertility = (tile.fertility - HARVEST_DEPLETION).max(0.);
+        }
+    }
+    /// Tiles in the order of the flattened `MapField`, as they are saved
+    pub fn tiles(&self) -> &[SoilTile] {
+        &self.tiles
+    }
+    /// Saves without the soil start from the soil of a new map, `None` if the saved tiles
+    /// do not fit the map or hold values out of range
+    pub fn from_save(tiles: Vec<SoilTile>, map: &MapField) -> Option<Self> {
+        let mut soil = Soil::new(map);
+        if tiles.is_empty() {
+            return Some(soil);
+        }
+        let is_valid = tiles.len() == soil.tiles.len()
+            && tiles
+                .iter()
+                .all(|t| (0. ..=1.).contains(&t.moisture) && (0. ..=1.).contains(&t.fertility));
+        if !is_valid {
+            return None;
+        }
+        soil.tiles = tiles;
+        Some(soil)
+    }
+    /// Moisture spreads from the water and between the neighbours, the weather wets
+    /// or dries the whole map
+    fn update(&mut self, map: &MapField, weather_moisture: f32) {
+        let moisture: Vec<f32> = self.tiles.iter().map(|t| t.moisture).collect();
+        let (width, height) = MAP_DIMENSION;
+        for x in 0..width {
+            for y in 0..height {
+                let i = x * height + y;
+                let tile = &mut self.tiles[i];
+                tile.fertility = (tile.fertility + FALLOW_RECOVERY).min(1.);
+                if is_wet(map[x][y]) {
+                    tile.moisture = 1.;
+                    continue;
+                }
+                let neighbours: Vec<f32> = [
+                    (x > 0).then(|| moisture[i - height]),
+                    (x + 1 < width).then(|| moisture[i + height]),
+                    (y > 0).then(|| moisture[i - 1]),
+                    (y + 1 < height).then(|| moisture[i + 1]),
+                ]
+                .into_iter()
+                .flatten()
+                .collect();
+                let average = neighbours.iter().sum::<f32>() / neighbours.len() as f32;
+                let spread = (average - tile.moisture) * DIFFUSION;
+                tile.moisture = (tile.moisture + spread + weather_moisture).clamp(0., 1.);
+            }
+        }
+    }
+}
+
+pub fn process_soil(
+    clock: Res<GameClock>,
+    map: Res<MapField>,
+    weather: Res<Weather>,
+    mut soil: ResMut<Soil>,
+) {
+    soil.until_update -= clock.delta();
+    if soil.until_update > 0. {
+        return;
+    }
+    soil.until_update += UPDATE_TIME;
+    soil.update(&map, weather.kind().moisture());
+}
@@ -0,0 +1,337 @@
+use core::fmt;
+
+use edict::{entity::EntityId, query::Entities, world::World};
+use rltk::{Point, RGBA};
+
+use crate::{
+    ambience::map::{MapField, MapRevision, MapTile, map_tile, nearest_walkable, set_water_frozen},
+    components::{Mover, PathFollower, PendingWork, Plant},
+    flow_timer::{cancel_timer, remaining_time, reschedule_timer},
+    game_clock::GameClock,
+    jobs::fail_jobs,
+    message_log::{Severity, post_message},
+};
+
+/// Game milliseconds of a whole day, four minutes at the normal speed
+pub const DAY_LENGTH: f32 = 240_000.;
+/// The game starts in the morning
+const START_HOUR: f32 = 6.;
+const MONTH_DAYS: u32 = 5;
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+/// The game starts on the first of March
+const START_DAY: u32 = 2 * MONTH_DAYS;
+const YEAR_DAYS: u32 = MONTHS.len() as u32 * MONTH_DAYS;
+/// December, January and February
+const WINTER_DAYS: u32 = 3 * MONTH_DAYS;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DayPhase {
+    Night,
+    Dawn,
+    Day,
+    Dusk,
+}
+
+impl fmt::Display for DayPhase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Season {
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+}
+
+impl Season {
+    /// Crops are sown in spring and summer only
+    pub fn is_sowing(&self) -> bool {
+        matches!(self, Season::Spring | Season::Summer)
+    }
+}
+
+impl fmt::Display for Season {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// Ambient light through the day, linearly blended between the hours
+const LIGHT_KEYS: [(f32, (f32, f32, f32)); 8] = [
+    (0., (0.25, 0.25, 0.45)),
+    (4.5, (0.25, 0.25, 0.45)),
+    (6., (0.85, 0.65, 0.55)),
+    (9., (1., 1., 1.)),
+    (17., (1., 1., 1.)),
+    (19., (0.9, 0.55, 0.45)),
+    (21., (0.25, 0.25, 0.45)),
+    (24., (0.25, 0.25, 0.45)),
+];
+
+/// Time of day and date, kept in step with the flow timers by the game clock
+pub struct Calendar {
+    /// Game milliseconds since the start of the game
+    time: f32,
+}
+
+impl Calendar {
+    pub fn new(time: f32) -> Self {
+        Calendar { time }
+    }
+    fn elapsed_hours(&self) -> f32 {
+        START_HOUR + self.time / DAY_LENGTH * 24.
+    }
+    /// Days since the start of the game, the first one is 0
+    pub fn days(&self) -> u32 {
+        (self.elapsed_hours() / 24.) as u32
+    }
+    /// Days since the first of January of the first year
+    fn calendar_days(&self) -> u32 {
+        START_DAY + self.days()
+    }
+    /// Month of the year from 0
+    fn month(&self) -> usize {
+        (self.calendar_days() % YEAR_DAYS / MONTH_DAYS) as usize
+    }
+    pub fn year(&self) -> u32 {
+        self.calendar_days() / YEAR_DAYS + 1
+    }
+    pub fn season(&self) -> Season {
+        match self.month() {
+            2..=4 => Season::Spring,
+            5..=7 => Season::Summer,
+            8..=10 => Season::Autumn,
+            _ => Season::Winter,
+        }
+    }
+    /// Game time of the first of December and of the first of March around the current
+    /// winter, `None` out of winter
+    pub fn winter(&self) -> Option<(f32, f32)> {
+        if self.season() != Season::Winter {
+            return None;
+        }
+        let day_of_year = self.calendar_days() % YEAR_DAYS;
+        let days_left = (YEAR_DAYS + START_DAY - day_of_year) % YEAR_DAYS;
+        let hours = (self.days() + days_left) as f32 * 24. - START_HOUR;
+        let end = hours / 24. * DAY_LENGTH;
+        Some((end - WINTER_DAYS as f32 * DAY_LENGTH, end))
+    }
+    /// Hour of the day from 0 to 24
+    pub fn hour(&self) -> f32 {
+        self.elapsed_hours() % 24.
+    }
+    pub fn phase(&self) -> DayPhase {
+        match self.hour() {
+            h if h < 4.5 => DayPhase::Night,
+            h if h < 9. => DayPhase::Dawn,
+            h if h < 17. => DayPhase::Day,
+            h if h < 21. => DayPhase::Dusk,
+            _ => DayPhase::Night,
+        }
+    }
+    /// Hours, minutes and the phase of the day
+    pub fn time_of_day(&self) -> String {
+        let hour = self.hour();
+        let minute = (hour.fract() * 60.) as u32;
+        format!("{:02}:{minute:02} {}", hour as u32, self.phase())
+    }
+    /// Color multiplier of everything lit only by the sky
+    pub fn ambient_light(&self) -> RGBA {
+        let hour = self.hour();
+        let i = LIGHT_KEYS
+            .windows(2)
+            .position(|w| hour < w[1].0)
+            .unwrap_or(LIGHT_KEYS.len() - 2);
+        let ((from_hour, from), (to_hour, to)) = (LIGHT_KEYS[i], LIGHT_KEYS[i + 1]);
+        let t = ((hour - from_hour) / (to_hour - from_hour)).clamp(0., 1.);
+        let blend = |a: f32, b: f32| a + (b - a) * t;
+        RGBA::from_f32(
+            blend(from.0, to.0),
+            blend(from.1, to.1),
+            blend(from.2, to.2),
+            1.,
+        )
+    }
+}
+
+impl fmt::Display for Calendar {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let day = self.calendar_days() % MONTH_DAYS + 1;
+        write!(f, "{day} {}, year {}", MONTHS[self.month()], self.year())
+    }
+}
+
+/// Water freezes for the winter and thaws in spring, the plants stop growing through it
+pub fn advance_calendar(world: &mut World) {
+    let delta = world.expect_resource::<GameClock>().delta();
+    let (season, new_season) = {
+        let mut calendar = world.expect_resource_mut::<Calendar>();
+        let season = calendar.season();
+        calendar.time += delta;
+        (season, calendar.season())
+    };
+    if new_season == season {
+        return;
+    }
+    let is_winter = new_season == Season::Winter;
+    if is_winter || season == Season::Winter {
+        set_water_frozen(&mut world.expect_resource_mut::<MapField>(), is_winter);
+        world.expect_resource_mut::<MapRevision>().bump();
+    }
+    if is_winter {
+        pause_growth(world);
+    } else if season == Season::Winter {
+        clear_thawed_water(world);
+    }
+    let text = match new_season {
+        Season::Winter => "Winter comes, the water freezes".to_string(),
+        Season::Spring => "Spring comes, the ice melts and sowing begins".to_string(),
+        Season::Autumn => "Autumn comes, the sowing is over".to_string(),
+        other => format!("{other} comes"),
+    };
+    post_message(world, Severity::Info, text);
+}
+
+/// Nothing stays on the melted ice: the creatures get out onto the nearest land, the jobs
+/// planned there fail and the rest sinks
+fn clear_thawed_water(world: &mut World) {
+    let map = *world.expect_resource::<MapField>();
+    let is_water = |p: Point| map_tile(&map, p) == Some(MapTile::Water);
+    let stranded: Vec<(EntityId, Point)> = world
+        .view::<(Entities, &Point, &Mover)>()
+        .iter()
+        .filter(|(_, p, _)| is_water(**p))
+        .map(|(e, p, _)| (e.id(), *p))
+        .collect();
+    for (id, pos) in stranded {
+        let Some(land) = nearest_walkable(&map, pos) else {
+            continue;
+        };
+        if let Ok((p, mover, follower)) = world
+            .view::<(&mut Point, &mut Mover, Option<&mut PathFollower>)>()
+            .try_get_mut(id)
+        {
+            *p = land;
+            mover.halt();
+            if let Some(follower) = follower {
+                follower.stop();
+            }
+        }
+        if let Ok(work) = world.view::<&PendingWork>().try_get(id) {
+            cancel_timer(world, work.0);
+        }
+    }
+    fail_jobs(world, is_water, "the ice melted");
+    let sunk: Vec<EntityId> = world
+        .view::<(Entities, &Point)>()
+        .iter()
+        .filter(|(_, p)| is_water(**p))
+        .map(|(e, _)| e.id())
+        .collect();
+    if sunk.is_empty() {
+        return;
+    }
+    for &id in &sunk {
+        let _ = world.despawn(id);
+    }
+    post_message(
+        world,
+        Severity::Bad,
+        format!("{} things sink with the melting ice", sunk.len()),
+    );
+}
+
+/// Pushes the growth timers of the plants back by the part of the winter their stages
+/// overlap, the growth resumes in spring
+fn pause_growth(world: &World) {
+    let Some((winter_start, winter_end)) = world.expect_resource::<Calendar>().winter() else {
+        return;
+    };
+    for plant in world.view::<&mut Plant>().iter_mut() {
+        let Some(handle) = plant.timer() else {
+            continue;
+        };
+        let Some(remaining) = remaining_time(world, handle) else {
+            continue;
+        };
+        let delay = plant.wait_winter(winter_start, winter_end);
+        reschedule_timer(world, handle, remaining + delay);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Calendar, DAY_LENGTH, START_HOUR, Season};
+
+    /// Game time at the midnight starting the game day `days`
+    fn midnight(days: u32) -> f32 {
+        (days as f32 * 24. - START_HOUR) / 24. * DAY_LENGTH
+    }
+
+    #[test]
+    fn starts_on_first_of_march() {
+        let calendar = Calendar::new(0.);
+        assert_eq!(calendar.days(), 0);
+        assert_eq!(calendar.hour(), START_HOUR);
+        assert_eq!(calendar.season(), Season::Spring);
+        assert_eq!(calendar.to_string(), "1 March, year 1");
+    }
+
+    #[test]
+    fn day_changes_at_midnight() {
+        let before = Calendar::new(midnight(1) - 1000.);
+        let after = Calendar::new(midnight(1) + 1000.);
+        assert_eq!(before.days(), 0);
+        assert_eq!(after.days(), 1);
+        assert_eq!(after.to_string(), "2 March, year 1");
+    }
+
+    #[test]
+    fn month_and_season_change_together() {
+        // The 1st of June is 15 days after the 1st of March
+        let may = Calendar::new(midnight(15) - 1000.);
+        let june = Calendar::new(midnight(15) + 1000.);
+        assert_eq!(may.to_string(), "5 May, year 1");
+        assert_eq!(may.season(), Season::Spring);
+        assert_eq!(june.to_string(), "1 June, year 1");
+        assert_eq!(june.season(), Season::Summer);
+    }
+
+    #[test]
+    fn year_changes_in_winter() {
+        let december = Calendar::new(midnight(50) - 1000.);
+        let january = Calendar::new(midnight(50) + 1000.);
+        assert_eq!(december.to_string(), "5 December, year 1");
+        assert_eq!(january.to_string(), "1 January, year 2");
+        assert_eq!(january.season(), Season::Winter);
+    }
+
+    #[test]
+    fn winter_spans_december_to_march() {
+        let (start, end) = (midnight(45), midnight(60));
+        assert_eq!(Calendar::new(start - 1000.).winter(), None);
+        assert_eq!(Calendar::new(end + 1000.).winter(), None);
+        for time in [start + 1000., midnight(52) + DAY_LENGTH / 2., end - 1000.] {
+            let (winter_start, winter_end) =
+                Calendar::new(time).winter().expect("The time is in winter");
+            assert!((winter_start - start).abs() < 1.);
+            assert!((winter_end - end).abs() < 1.);
+        }
+    }
+}
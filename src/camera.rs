@@ -1,16 +1,28 @@
 use std::cmp::{max, min};
 
-use rltk::{DrawBatch, Point, Rect};
+use edict::{entity::EntityId, query::Entities};
+use rltk::{ColorPair, DrawBatch, Point, RGBA, Rect};
 
 use crate::{
     State,
-    ambience::map::{MAP_BORDER, MapField, MapTile},
-    components::Renderable,
+    ambience::{
+        map::{MAP_BORDER, MapField, MapTile},
+        soil::{Soil, SoilTile},
+    },
+    calendar::{Calendar, Season},
+    components::{Mover, Renderable},
+    control::ControlMode,
+    flow_timer::current_time,
+    fov::{RevealedTiles, Viewshed},
     gui::layout::MAIN_VIEW_POSITION,
+    lighting::Lighting,
     terminal_constants::{CHAR_Z_ORDER, MAP_Z_ORDER},
+    weather::Weather,
 };
 
 const OFFSET_FROM_PLAYER: Point = Point { x: 15, y: 10 };
+/// Map cells the free looking camera moves per movement key
+pub const PAN_STEP: i32 = 3;
 const CAMERA_BORDER: Rect = camera_border();
 
 const fn camera_border() -> Rect {
@@ -26,28 +38,43 @@ const fn camera_border() -> Rect {
 }
 pub struct Camera {
     position: Point,
+    /// Panned by the player instead of following what is controlled
+    is_free_look: bool,
 }
 
 impl Camera {
     pub fn new(position: Point) -> Self {
-        Camera { position }
+        Camera {
+            position,
+            is_free_look: false,
+        }
     }
 
-    pub fn _get_position(&self) -> &Point {
+    pub fn get_position(&self) -> &Point {
         &self.position
     }
     pub fn get_start_view(&self) -> Point {
         self.position - MAIN_VIEW_POSITION.center()
     }
+    pub fn is_free_look(&self) -> bool {
+        self.is_free_look
+    }
+    fn clamp_to_border(&mut self) {
+        self.position.x = min(CAMERA_BORDER.x2, max(CAMERA_BORDER.x1, self.position.x));
+        self.position.y = min(CAMERA_BORDER.y2, max(CAMERA_BORDER.y1, self.position.y));
+    }
 }
 
+/// Keeps what is controlled, the player or the cursor corner being moved, near the center
 pub fn move_camera(gs: &State) {
-    let pos = gs.world.view::<&Point>();
-    let pos = pos.try_get(gs.player_id).expect("Player not exists");
+    let pos = followed_point(gs);
     let mut camera = gs
         .world
         .get_resource_mut::<Camera>()
         .expect("Camera resource not exists");
+    if camera.is_free_look {
+        return;
+    }
     camera.position.x = min(
         pos.x + OFFSET_FROM_PLAYER.x,
         max(pos.x - OFFSET_FROM_PLAYER.x, camera.position.x),
@@ -58,43 +85,187 @@ pub fn move_camera(gs: &State) {
         max(pos.y - OFFSET_FROM_PLAYER.y, camera.position.y),
     );
 
-    camera.position.x = min(CAMERA_BORDER.x2, max(CAMERA_BORDER.x1, camera.position.x));
-    camera.position.y = min(CAMERA_BORDER.y2, max(CAMERA_BORDER.y1, camera.position.y));
+    camera.clamp_to_border();
+}
+
+fn followed_point(gs: &State) -> Point {
+    let cursor = gs.world.view::<&Rect>();
+    let cursor = cursor.try_get(gs.cursor_id).expect("Cursor not exists");
+    match gs.mode {
+        ControlMode::Cursor => Point::new(cursor.x1, cursor.y1),
+        ControlMode::Corner => Point::new(cursor.x2, cursor.y2),
+        ControlMode::Player | ControlMode::Auto => {
+            let pos = gs.world.view::<&Point>();
+            *pos.try_get(gs.player_id).expect("Player not exists")
+        }
+    }
+}
+
+/// Centers the free looking camera on `target`
+pub fn jump_camera(gs: &State, target: Point) {
+    let mut camera = gs
+        .world
+        .get_resource_mut::<Camera>()
+        .expect("Camera resource not exists");
+    camera.position = target;
+    camera.is_free_look = true;
+    camera.clamp_to_border();
+}
+
+pub fn pan_camera(gs: &State, delta: Point) {
+    let mut camera = gs
+        .world
+        .get_resource_mut::<Camera>()
+        .expect("Camera resource not exists");
+    camera.position += delta;
+    camera.is_free_look = true;
+    camera.clamp_to_border();
+}
+
+pub fn toggle_free_look(gs: &State) {
+    let is_free_look = {
+        let mut camera = gs
+            .world
+            .get_resource_mut::<Camera>()
+            .expect("Camera resource not exists");
+        camera.is_free_look = !camera.is_free_look;
+        camera.is_free_look
+    };
+    if !is_free_look {
+        snap_camera(gs);
+    }
 }
 
-pub fn render_map(gs: &State, draw_batch: &mut DrawBatch) {
+/// Leaves the free look and centers on the player
+pub fn snap_camera(gs: &State) {
+    let pos = gs.world.view::<&Point>();
+    let pos = *pos.try_get(gs.player_id).expect("Player not exists");
+    let mut camera = gs
+        .world
+        .get_resource_mut::<Camera>()
+        .expect("Camera resource not exists");
+    camera.position = pos;
+    camera.is_free_look = false;
+    camera.clamp_to_border();
+}
+
+pub fn render_map(gs: &State, lighting: &Lighting, draw_batch: &mut DrawBatch) {
     let map_field = gs.world.get_resource::<MapField>().unwrap();
-    let start_view_correction =
-        gs.get_camera_start_view() - Point::new(MAP_BORDER.x1, MAP_BORDER.y1);
+    let start_view = gs.get_camera_start_view();
+    let start_view_correction = start_view - Point::new(MAP_BORDER.x1, MAP_BORDER.y1);
+    let sight = gs.world.view::<&Viewshed>();
+    let sight = sight.try_get(gs.player_id).ok();
+    let revealed = gs.world.expect_resource::<RevealedTiles>();
+    let unexplored = ColorPair::new(rltk::BLACK, rltk::BLACK);
+    let grass = grass_color(gs.world.expect_resource::<Calendar>().season());
+    let weather = gs.world.expect_resource::<Weather>();
+    let time = current_time(&gs.world);
+    let soil = gs.world.expect_resource::<Soil>();
 
     for p in MAIN_VIEW_POSITION.point_set() {
+        let pos = p + start_view;
+        let is_visible = sight.is_some_and(|s| s.is_visible(pos));
+        if !is_visible && !revealed.is_revealed(pos) {
+            draw_batch.set(p, unexplored, ' ');
+            continue;
+        }
         let (m, n) = (p + start_view_correction).to_unsigned_tuple();
-        let (sym, color) = match map_field[m][n] {
-            MapTile::Grass => (
-                'ш',
-                rltk::ColorPair::new(rltk::LIGHT_GREEN, rltk::SANDY_BROWN),
-            ),
+        let tile = map_field[m][n];
+        if gs.is_soil_shown
+            && tile.properties().fertile
+            && let Some(soil) = soil.get(pos)
+        {
+            let (sym, color) = soil_cell(soil);
+            draw_batch.set(p, color, sym);
+            continue;
+        }
+        let (sym, color) = match tile {
+            MapTile::Grass => ('ш', grass),
             MapTile::Ground => (
                 '_',
                 rltk::ColorPair::new(rltk::SANDY_BROWN, rltk::SANDY_BROWN),
             ),
             MapTile::Water => ('~', rltk::ColorPair::new(rltk::ALICEBLUE, rltk::BLUE)),
+            MapTile::Forest => (
+                '♣',
+                rltk::ColorPair::new(rltk::FOREST_GREEN, rltk::DARK_GREEN),
+            ),
+            MapTile::Ice => (
+                '=',
+                rltk::ColorPair::new(rltk::WHITE, rltk::LIGHT_STEEL_BLUE),
+            ),
+            MapTile::Fence => (
+                '#',
+                rltk::ColorPair::new(rltk::SADDLE_BROWN, rltk::SANDY_BROWN),
+            ),
+        };
+        // Precipitation falls over the tiles in sight, under the creatures
+        let (sym, color) = match weather.precipitation(pos, time).filter(|_| is_visible) {
+            Some((drop, fg)) => (drop, ColorPair::new(fg, color.bg)),
+            None => (sym, color),
+        };
+        let color = if is_visible {
+            lighting.tint(pos, color)
+        } else {
+            remembered(color)
         };
         draw_batch.set(p, color, sym);
     }
     draw_batch.submit(MAP_Z_ORDER).expect("Batch error");
 }
-pub fn render_dynamic(gs: &State, draw_batch: &mut DrawBatch) {
+
+/// Soil overlay cell, wetter soil is bluer and richer soil greener
+fn soil_cell(soil: &SoilTile) -> (char, ColorPair) {
+    let dry = RGBA::named(rltk::BURLYWOOD);
+    let bg = dry.lerp(RGBA::named(rltk::STEEL_BLUE), soil.moisture);
+    let fg = RGBA::named(rltk::GREY).lerp(RGBA::named(rltk::LIME_GREEN), soil.fertility);
+    let sym = if soil.is_tilled { '≡' } else { '·' };
+    (sym, ColorPair::new(fg, bg))
+}
+
+fn grass_color(season: Season) -> ColorPair {
+    match season {
+        Season::Spring => ColorPair::new(rltk::LIGHT_GREEN, rltk::SANDY_BROWN),
+        Season::Summer => ColorPair::new(rltk::LAWN_GREEN, rltk::SANDY_BROWN),
+        Season::Autumn => ColorPair::new(rltk::GOLDENROD, rltk::PERU),
+        Season::Winter => ColorPair::new(rltk::WHITE, rltk::GAINSBORO),
+    }
+}
+
+/// Dimmed grey colors of tiles out of sight
+fn remembered(color: ColorPair) -> ColorPair {
+    let black = RGBA::named(rltk::BLACK);
+    ColorPair::new(
+        color.fg.to_greyscale().lerp(black, 0.5),
+        color.bg.to_greyscale().lerp(black, 0.5),
+    )
+}
+
+/// Creatures are shown in the sight of the player, still objects on explored tiles
+pub fn is_shown(gs: &State, entity: EntityId, pos: Point, is_creature: bool) -> bool {
+    if entity == gs.player_id {
+        return true;
+    }
+    if is_creature {
+        let sight = gs.world.view::<&Viewshed>();
+        let sight = sight.try_get(gs.player_id);
+        sight.is_ok_and(|s| s.is_visible(pos))
+    } else {
+        gs.world.expect_resource::<RevealedTiles>().is_revealed(pos)
+    }
+}
+pub fn render_dynamic(gs: &State, lighting: &Lighting, draw_batch: &mut DrawBatch) {
     let start_view = gs.get_camera_start_view();
 
     gs.world
-        .view::<(&Point, &Renderable)>()
+        .view::<(Entities, &Point, &Renderable, Option<&Mover>)>()
         .iter()
-        .filter(|(_, ren)| ren.is_visible)
-        .map(|(pos, ren)| (*pos - start_view, ren))
-        .filter(|(pos, _)| MAIN_VIEW_POSITION.point_in_rect(*pos))
-        .for_each(|(pos, ren)| {
-            draw_batch.set(pos, ren.color, ren.glyph);
+        .filter(|(_, _, ren, _)| ren.is_visible)
+        .filter(|(e, pos, _, mover)| is_shown(gs, e.id(), **pos, mover.is_some()))
+        .map(|(_, pos, ren, _)| (*pos, *pos - start_view, ren))
+        .filter(|(_, view_pos, _)| MAIN_VIEW_POSITION.point_in_rect(*view_pos))
+        .for_each(|(pos, view_pos, ren)| {
+            draw_batch.set(view_pos, lighting.tint(pos, ren.color), ren.glyph);
         });
     draw_batch.submit(CHAR_Z_ORDER).expect("Batch error");
 }
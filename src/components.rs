@@ -1,13 +1,31 @@
 use edict::{
+    entity::EntityId,
     flow::FlowEntity,
-    prelude::{Component, Res},
+    prelude::{Component, Res, ResMut},
+    query::Entities,
     view::View,
+    world::World,
 };
 use rltk::{ColorPair, FontCharType, Point, RGBA, RandomNumberGenerator};
+use serde::{Deserialize, Serialize};
 
-use crate::{ambience::map::MAP_BORDER, flow_timer::wait_pause_entity};
+use crate::{
+    ambience::{
+        map::{MapField, is_walkable, map_tile},
+        path::find_path,
+        soil::Soil,
+    },
+    calendar::{Calendar, DayPhase},
+    crops::Crops,
+    flow_timer::{FlowTimerMap, TimerHandle, get_current_time, start_timer, wait_timer},
+    fov::Viewshed,
+    game_clock::GameClock,
+    inventory::Inventory,
+    message_log::{MessageLog, Severity, post_message},
+    weather::Weather,
+};
 
-#[derive(Component)]
+#[derive(Component, Serialize, Deserialize)]
 pub struct Renderable {
     pub is_visible: bool,
     pub glyph: FontCharType,
@@ -38,34 +56,191 @@ impl Renderable {
     }
 }
 
-#[derive(Component)]
-pub struct RenderStack {}
+/// Growth state of a plant, kept on the entity so the flow can be resumed after load
+#[derive(Component, Serialize, Deserialize)]
+pub struct Plant {
+    crop: String,
+    stage: usize,
+    stage_start: Option<f32>,
+    /// Growth speed through the current stage, set by the weather when it starts
+    #[serde(default = "normal_growth")]
+    growth_rate: f32,
+    /// Timer of the current stage, started again by the flow after load
+    #[serde(skip)]
+    timer: Option<TimerHandle>,
+}
 
-impl RenderStack {
-    const RENDER_DATA: [(char, (u8, u8, u8)); 5] = [
-        ('.', rltk::YELLOW),
-        ('i', rltk::GREEN),
-        ('|', rltk::GREEN),
-        ('T', rltk::GREEN),
-        ('T', rltk::YELLOW),
-    ];
+fn normal_growth() -> f32 {
+    1.
+}
+
+impl Plant {
+    pub fn new(crop: &str) -> Self {
+        Plant {
+            crop: crop.to_string(),
+            stage: 0,
+            stage_start: None,
+            growth_rate: normal_growth(),
+            timer: None,
+        }
+    }
+    pub fn crop(&self) -> &str {
+        &self.crop
+    }
+    pub fn stage(&self) -> usize {
+        self.stage
+    }
+    /// Game time when the current stage ends, `None` before the flow has started it
+    pub fn stage_end(&self, crops: &Crops) -> Option<f32> {
+        let stage = crops.stage(&self.crop, self.stage)?;
+        Some(self.stage_start? + stage.duration / self.growth_rate)
+    }
+    fn start_stage(&mut self, time: f32, growth_rate: f32) {
+        self.stage_start = Some(time);
+        self.growth_rate = growth_rate;
+    }
+    /// Timer of the stage the plant is growing through
+    pub fn timer(&self) -> Option<TimerHandle> {
+        self.timer
+    }
+    /// Pushes the current stage back by the part of the winter it overlaps, returns the delay
+    pub fn wait_winter(&mut self, winter_start: f32, winter_end: f32) -> f32 {
+        let Some(start) = self.stage_start else {
+            return 0.;
+        };
+        let delay = winter_end - start.max(winter_start);
+        self.stage_start = Some(start + delay);
+        delay
+    }
+    /// The plant has reached the last stage of its crop
+    pub fn is_ripe(&self, crops: &Crops) -> bool {
+        crops
+            .get(&self.crop)
+            .is_some_and(|c| self.stage + 1 >= c.stages.len())
+    }
+}
+
+/// Removes the ripe plant at `pos` and puts its yield into the worker inventory
+pub fn harvest(world: &mut World, worker: EntityId, pos: Point) -> bool {
+    let ripe = {
+        let crops = world.expect_resource::<Crops>();
+        world
+            .view::<(Entities, &Point, &Plant)>()
+            .iter()
+            .find(|(_, p, plant)| **p == pos && plant.is_ripe(&crops))
+            .map(|(e, _, plant)| (e.id(), plant.crop.clone()))
+    };
+    let Some((plant_id, crop)) = ripe else {
+        return false;
+    };
+    let amount = world
+        .expect_resource::<Crops>()
+        .get(&crop)
+        .map_or(0, |c| c.yield_amount);
+    if let Ok(inventory) = world.view::<&mut Inventory>().try_get_mut(worker) {
+        inventory.add(&crop, amount);
+    }
+    let _ = world.despawn(plant_id);
+    world.expect_resource_mut::<Soil>().deplete(pos);
+    post_message(world, Severity::Good, format!("Harvested {amount} {crop}"));
+    true
 }
 
 pub async fn rotate_render_stack(flow_entity: FlowEntity) {
-    for i in RenderStack::RENDER_DATA {
-        flow_entity.map(|mut er| {
+    loop {
+        let (crop, stage_index, pos) = flow_entity.map(|mut er| {
+            let pos = *er.get::<&Point>().unwrap();
+            let plant = er.get_mut::<&mut Plant>().unwrap();
+            (plant.crop.clone(), plant.stage, pos)
+        });
+        let stage = flow_entity.world().map(|w| {
+            let crops = w.expect_resource::<Crops>();
+            crops
+                .get(&crop)
+                .map(|_| crops.stage(&crop, stage_index).cloned())
+        });
+        // The crop was removed from the data file, the plant would never grow
+        let Some(stage) = stage else {
+            let id = flow_entity.id();
+            flow_entity.world().map(|w| {
+                let _ = w.despawn(id);
+                post_message(
+                    w,
+                    Severity::Warning,
+                    format!("Unknown crop {crop} is removed"),
+                );
+            });
+            return;
+        };
+        // The plant has gone through all the stages
+        let Some(stage) = stage else {
+            return;
+        };
+        let now = get_current_time(&flow_entity.world());
+        let growth_rate = flow_entity.world().map(|w| crop_growth_rate(w, pos));
+        let stage_end = flow_entity.map(|mut er| {
+            let plant = er.get_mut::<&mut Plant>().unwrap();
+            if plant.stage_start.is_none() {
+                plant.start_stage(now, growth_rate);
+            }
+            let stage_end = plant.stage_start.unwrap_or(now) + stage.duration / plant.growth_rate;
             let r = er.get_mut::<&mut Renderable>().unwrap();
             r.is_visible = true;
-            r.glyph = i.0 as u16;
-            r.color.fg = RGBA::named(i.1);
+            r.glyph = stage.glyph as u16;
+            r.color.fg = RGBA::named(stage.color);
+            stage_end
+        });
+        let id = flow_entity.id();
+        let handle = flow_entity
+            .world()
+            .map(|w| start_timer(w, Some(id), stage_end - now));
+        flow_entity.map(|mut er| er.get_mut::<&mut Plant>().unwrap().timer = Some(handle));
+        if !wait_timer(flow_entity.world(), handle).await {
+            return;
+        }
+        let (is_withering, growth_rate) = flow_entity.world().map(|w| {
+            (
+                w.expect_resource::<Weather>().is_withering(),
+                crop_growth_rate(w, pos),
+            )
+        });
+        if is_withering {
+            let id = flow_entity.id();
+            flow_entity.world().map(|w| {
+                let _ = w.despawn(id);
+                post_message(
+                    w,
+                    Severity::Bad,
+                    format!("The {crop} withers in the drought"),
+                );
+            });
+            return;
+        }
+        flow_entity.map(|mut er| {
+            let plant = er.get_mut::<&mut Plant>().unwrap();
+            // The winter may have pushed the end back while the timer was running
+            let stage_end = plant.stage_start.unwrap_or(now) + stage.duration / plant.growth_rate;
+            plant.stage += 1;
+            plant.start_stage(stage_end, growth_rate);
         });
-        wait_pause_entity(flow_entity, 1000f32).await;
     }
 }
 
+/// Growth speed of a crop at `pos`, from the weather and the soil
+fn crop_growth_rate(world: &World, pos: Point) -> f32 {
+    let weather = world.expect_resource::<Weather>().kind().growth_rate();
+    weather * world.expect_resource::<Soil>().growth_rate(pos)
+}
+
+/// Timer of the work the entity is busy with, cancelled when the work is interrupted
+#[derive(Component)]
+pub struct PendingWork(pub TimerHandle);
+
+/// Marks the entity controlled by the player, the one whose sight reveals the map
 #[derive(Component)]
-pub struct PlanJob {}
+pub struct Player;
 
+#[derive(Serialize, Deserialize, Debug)]
 enum Direction {
     Left,
     Right,
@@ -73,11 +248,25 @@ enum Direction {
     Down,
 }
 
-#[derive(Component)]
+impl Direction {
+    fn delta(&self) -> Point {
+        match self {
+            Direction::Left => Point::new(-1, 0),
+            Direction::Right => Point::new(1, 0),
+            Direction::Top => Point::new(0, -1),
+            Direction::Down => Point::new(0, 1),
+        }
+    }
+}
+
+#[derive(Component, Serialize, Deserialize)]
 pub struct Mover {
     offset: f32,
     speed: f32,
     direction: Option<Direction>,
+    /// Step taken right after the current one
+    #[serde(default)]
+    queued: Option<Direction>,
 }
 
 impl Mover {
@@ -86,6 +275,7 @@ impl Mover {
             offset: 0.,
             speed,
             direction: None,
+            queued: None,
         }
     }
     pub fn move_left(&mut self) {
@@ -100,31 +290,71 @@ impl Mover {
     pub fn move_down(&mut self) {
         self.direction.get_or_insert(Direction::Down);
     }
+    pub fn step_towards(&mut self, delta: Point) {
+        match (delta.x.signum(), delta.y.signum()) {
+            (-1, _) => self.move_left(),
+            (1, _) => self.move_right(),
+            (_, -1) => self.move_top(),
+            (_, 1) => self.move_down(),
+            _ => {}
+        }
+    }
+    /// Steps towards `delta` now or right after the current step, a newer key
+    /// replaces the queued step
+    pub fn queue_step(&mut self, delta: Point) {
+        if !self.is_moving() {
+            self.step_towards(delta);
+            return;
+        }
+        self.queued = match (delta.x.signum(), delta.y.signum()) {
+            (-1, _) => Some(Direction::Left),
+            (1, _) => Some(Direction::Right),
+            (_, -1) => Some(Direction::Top),
+            (_, 1) => Some(Direction::Down),
+            _ => return,
+        };
+    }
+    pub fn is_moving(&self) -> bool {
+        self.direction.is_some()
+    }
+    pub fn direction_name(&self) -> Option<String> {
+        self.direction.as_ref().map(|d| format!("{d:?}"))
+    }
     fn stop(&mut self) {
+        self.offset = 0.;
+        self.direction = self.queued.take();
+    }
+    /// Drops the current and the queued step, the entity was put elsewhere
+    pub fn halt(&mut self) {
         self.offset = 0.;
         self.direction = None;
+        self.queued = None;
     }
 }
 
-pub fn process_mover(v: View<(&mut Point, &mut Mover)>, d: Res<f32>) {
-    for (p, m) in v {
-        if m.direction.is_none() {
-            return;
-        }
+pub fn process_mover(
+    v: View<(&mut Point, &mut Mover, Option<&PendingWork>)>,
+    clock: Res<GameClock>,
+    map: Res<MapField>,
+    mut timers: ResMut<FlowTimerMap>,
+) {
+    for (p, m, work) in v {
+        let Some(direction) = &m.direction else {
+            continue;
+        };
         let no_half = m.offset < 0.5;
-        m.offset += m.speed * *d;
+        let destination = if no_half { *p + direction.delta() } else { *p };
+        let Some(tile) = map_tile(&map, destination).filter(|t| t.properties().walkable) else {
+            m.stop();
+            continue;
+        };
+        m.offset += m.speed * clock.delta() / tile.properties().move_cost;
         if no_half && m.offset >= 0.5 {
-            let (delta_x, delta_y) = match &m.direction {
-                None => unreachable!(),
-                Some(direction) => match direction {
-                    Direction::Left => (-1, 0),
-                    Direction::Right => (1, 0),
-                    Direction::Top => (0, -1),
-                    Direction::Down => (0, 1),
-                },
-            };
-            p.x = (p.x + delta_x).clamp(MAP_BORDER.x1, MAP_BORDER.x2 - 1);
-            p.y = (p.y + delta_y).clamp(MAP_BORDER.y1, MAP_BORDER.y2 - 1);
+            *p = destination;
+            // Any step interrupts the work, whether walked, followed or pushed
+            if let Some(work) = work {
+                timers.cancel(work.0);
+            }
         }
         if m.offset > 1. {
             m.stop();
@@ -132,26 +362,130 @@ pub fn process_mover(v: View<(&mut Point, &mut Mover)>, d: Res<f32>) {
     }
 }
 
-#[derive(Component)]
+#[derive(Component, Serialize, Deserialize)]
+pub struct PathFollower {
+    target: Option<Point>,
+    path: Vec<Point>,
+}
+
+impl PathFollower {
+    pub fn new() -> Self {
+        PathFollower {
+            target: None,
+            path: Vec::new(),
+        }
+    }
+    pub fn go_to(&mut self, target: Point) {
+        self.target = Some(target);
+        self.path.clear();
+    }
+    pub fn stop(&mut self) {
+        self.target = None;
+        self.path.clear();
+    }
+    pub fn target(&self) -> Option<Point> {
+        self.target
+    }
+}
+
+// Steps are fed to `Mover` one at a time, the path is replanned whenever the next step
+// is no longer adjacent (the mover was pushed or clamped) or became impassable
+pub fn process_path_follower(v: View<(&Point, &mut Mover, &mut PathFollower)>, map: Res<MapField>) {
+    for (p, m, f) in v {
+        let Some(target) = f.target else {
+            continue;
+        };
+        if m.is_moving() {
+            continue;
+        }
+        if *p == target {
+            f.stop();
+            continue;
+        }
+        let is_blocked = f.path.last().is_none_or(|next| {
+            (next.x - p.x).abs() + (next.y - p.y).abs() != 1 || !is_walkable(&map, *next)
+        });
+        if is_blocked {
+            match find_path(&map, *p, target) {
+                Some(path) => f.path = path.into_iter().rev().collect(),
+                None => {
+                    f.stop();
+                    continue;
+                }
+            }
+        }
+        if let Some(next) = f.path.pop() {
+            m.step_towards(next - *p);
+        }
+    }
+}
+
+#[derive(Component, Serialize, Deserialize)]
 pub struct HareBrain {
     last_choise: i32,
     rand: RandomNumberGenerator,
+    /// The hare sees the player and runs away from it
+    #[serde(default)]
+    is_alarmed: bool,
 }
 
 impl HareBrain {
-    pub fn new() -> Self {
-        let mut rand = RandomNumberGenerator::new();
+    pub fn new(mut rand: RandomNumberGenerator) -> Self {
         let last_choise = rand.range(0, 4);
-        HareBrain { last_choise, rand }
+        HareBrain {
+            last_choise,
+            rand,
+            is_alarmed: false,
+        }
+    }
+    /// Hares feed at twilight and through the night, in the day they lie low
+    /// and only run when alarmed
+    pub fn is_resting(&self, phase: DayPhase) -> bool {
+        phase == DayPhase::Day && !self.is_alarmed
+    }
+    /// Direction the hare keeps running in unless it changes its mind
+    pub fn heading(&self) -> &'static str {
+        match self.last_choise {
+            0 => "Left",
+            1 => "Right",
+            2 => "Top",
+            _ => "Down",
+        }
     }
 }
 
-pub fn process_hare_brain(v: View<(&mut Mover, &mut HareBrain)>) {
-    for (m, b) in v {
-        if m.direction.is_some() {
-            return;
+pub fn process_hare_brain(
+    v: View<(
+        &Point,
+        &mut Mover,
+        &mut HareBrain,
+        Option<&PathFollower>,
+        Option<&Viewshed>,
+    )>,
+    players: View<(&Point, &Player)>,
+    calendar: Res<Calendar>,
+    mut log: ResMut<MessageLog>,
+) {
+    for (p, m, b, f, sight) in v {
+        if m.direction.is_some() || f.is_some_and(|f| f.target().is_some()) {
+            continue;
         }
-        let direction = if b.rand.rand::<f32>() < 0.75 {
+        let seen_player = sight.and_then(|s| {
+            players
+                .iter()
+                .map(|(pp, _)| *pp)
+                .find(|pp| s.is_visible(*pp))
+        });
+        if seen_player.is_some() && !b.is_alarmed {
+            log.post(Severity::Info, "A hare notices you and bolts");
+        }
+        b.is_alarmed = seen_player.is_some();
+        if b.is_resting(calendar.phase()) {
+            continue;
+        }
+        let direction = if let Some(player) = seen_player {
+            flee_direction(*p - player)
+        } else if b.rand.rand::<f32>() < 0.75 {
             b.last_choise
         } else {
             b.rand.range(0, 4)
@@ -166,3 +500,14 @@ pub fn process_hare_brain(v: View<(&mut Mover, &mut HareBrain)>) {
         }
     }
 }
+
+/// Brain direction along the main axis of `away`
+fn flee_direction(away: Point) -> i32 {
+    if away.x.abs() >= away.y.abs() {
+        if away.x < 0 { 0 } else { 1 }
+    } else if away.y < 0 {
+        2
+    } else {
+        3
+    }
+}
@@ -4,12 +4,22 @@ use std::cmp::{max, min};
 use crate::{
     State,
     ambience::map::MAP_BORDER,
-    flow_timer::wait_pause_entity,
+    calendar::Calendar,
+    camera::{Camera, jump_camera, pan_camera, snap_camera, toggle_free_look},
+    components::{Mover, PathFollower, PendingWork, harvest},
+    crops::{Crops, SelectedCrop},
+    flow_timer::{cancel_timer, start_timer, wait_timer},
+    game_clock::{GameClock, GameSpeed},
+    gui::minimap::map_to_minimap,
+    input::Command,
+    jobs::{PlanJob, SelectedJob, cancel_jobs, crop_to_plant, has_job_at, work_on_jobs},
     math::QuasiRect,
+    message_log::{MessageLog, Severity, post_message},
+    save::{SAVE_PATH, SaveError, load_game, save_game},
     spawn::{create_plan_job, create_plant_flow},
 };
-use edict::query::Entities;
-use rltk::{Point, Rect, Rltk, VirtualKeyCode};
+use edict::{entity::EntityId, world::World};
+use rltk::{Point, Rect};
 
 #[derive(Hash, Eq, PartialEq, Debug)]
 pub enum ControlMode {
@@ -47,9 +57,21 @@ impl ControlMode {
     }
 
     fn switch_auto_mode(gs: &mut State) {
+        cancel_pending_work(&gs.world, gs.player_id);
         gs.mode = ControlMode::Auto
     }
 
+    fn stop_auto_mode(gs: &mut State) {
+        gs.mode = ControlMode::Player;
+        if let Ok(f) = gs
+            .world
+            .view::<&mut PathFollower>()
+            .try_get_mut(gs.player_id)
+        {
+            f.stop();
+        }
+    }
+
     fn process_moving(gs: &State, delta_x: i32, delta_y: i32) {
         match gs.mode {
             ControlMode::Player => Self::try_move_player(gs, delta_x, delta_y),
@@ -61,7 +83,7 @@ impl ControlMode {
 
     fn process_action(gs: &mut State) {
         match gs.mode {
-            ControlMode::Player => start_future(gs),
+            ControlMode::Player => harvest_or_plant(gs),
             ControlMode::Cursor => Self::process_action_on_cursor(gs),
             ControlMode::Corner => Self::process_action_on_cursor(gs),
             ControlMode::Auto => (),
@@ -74,19 +96,75 @@ impl ControlMode {
             .view::<&mut Rect>()
             .try_get_mut(gs.cursor_id)
             .expect("Cursor does not exist");
-        rect.envelop_rect()
-            .for_each(|p| create_plan_job(&mut gs.world, p, gs.player_id));
+        let (kind, priority) = {
+            let selected = gs.world.expect_resource::<SelectedJob>();
+            (selected.kind, selected.priority)
+        };
+        rect.envelop_rect().for_each(|p| {
+            if !has_job_at(&gs.world, gs.player_id, p) {
+                create_plan_job(&mut gs.world, p, gs.player_id, kind, priority);
+            }
+        });
     }
 
+    fn process_cancel(gs: &mut State) {
+        match gs.mode {
+            ControlMode::Player => Self::stop_player(gs),
+            ControlMode::Cursor | ControlMode::Corner => Self::process_cancel_on_cursor(gs),
+            ControlMode::Auto => (),
+        }
+    }
+
+    fn process_cancel_on_cursor(gs: &mut State) {
+        let &rect = gs
+            .world
+            .view::<&Rect>()
+            .try_get(gs.cursor_id)
+            .expect("Cursor does not exist");
+        cancel_jobs(&mut gs.world, gs.player_id, rect.envelop_rect());
+    }
+
+    fn stop_player(gs: &State) {
+        if let Ok(f) = gs
+            .world
+            .view::<&mut PathFollower>()
+            .try_get_mut(gs.player_id)
+        {
+            f.stop();
+        }
+        cancel_pending_work(&gs.world, gs.player_id);
+    }
+
+    fn walk_player(gs: &State, target: Point) {
+        cancel_pending_work(&gs.world, gs.player_id);
+        if let Ok(f) = gs
+            .world
+            .view::<&mut PathFollower>()
+            .try_get_mut(gs.player_id)
+        {
+            f.go_to(target);
+        }
+    }
+
+    fn designate(gs: &mut State, area: Rect) {
+        let mut rect = gs.world.view::<&mut Rect>();
+        let rect = rect
+            .try_get_mut(gs.cursor_id)
+            .expect("Cursor does not exist");
+        *rect = area;
+        gs.mode = ControlMode::Corner;
+    }
+
+    /// The step takes the time of the destination tile, a key pressed during it is queued
     fn try_move_player(gs: &State, delta_x: i32, delta_y: i32) {
-        let mut pos = gs.world.view::<&mut Point>();
+        Self::stop_player(gs);
+        let mut mover = gs.world.view::<&mut Mover>();
 
-        let pos = pos
+        let mover = mover
             .try_get_mut(gs.player_id)
             .expect("Player does not exist");
 
-        pos.x = (pos.x + delta_x).clamp(MAP_BORDER.x1, MAP_BORDER.x2 - 1);
-        pos.y = (pos.y + delta_y).clamp(MAP_BORDER.y1, MAP_BORDER.y2 - 1);
+        mover.queue_step(Point::new(delta_x, delta_y));
     }
 
     fn try_move_cursor_start(gs: &State, delta_x: i32, delta_y: i32) {
@@ -112,44 +190,163 @@ impl ControlMode {
     }
 }
 
-pub fn player_input(gs: &mut State, ctx: &mut Rltk) {
-    // Player movement
-    match ctx.key {
-        None => {} // Nothing happened
-
-        Some(key) => match key {
-            VirtualKeyCode::Left => ControlMode::process_moving(gs, -1, 0),
-            VirtualKeyCode::Right => ControlMode::process_moving(gs, 1, 0),
-            VirtualKeyCode::Up => ControlMode::process_moving(gs, 0, -1),
-            VirtualKeyCode::Down => ControlMode::process_moving(gs, 0, 1),
-            VirtualKeyCode::Return => ControlMode::process_action(gs),
-            VirtualKeyCode::P => ControlMode::switch_control_mode(gs),
-            VirtualKeyCode::A => ControlMode::switch_auto_mode(gs),
-            _ => {}
-        },
+/// Gameplay commands of the player go through the recording and are dropped during a replay
+pub fn player_command(gs: &mut State, command: Command) {
+    if command.is_gameplay() {
+        let tick = gs.world.expect_resource::<GameClock>().tick_count();
+        match gs.input.accept(command, tick) {
+            Ok(true) => {}
+            Ok(false) => return,
+            // The game goes on, only the recording misses the command
+            Err(e) => post_message(
+                &gs.world,
+                Severity::Warning,
+                format!("Input recording error: {e}"),
+            ),
+        }
+    }
+    apply_command(gs, command);
+}
+
+pub fn apply_command(gs: &mut State, command: Command) {
+    // Any manual gameplay command takes the control back from Auto mode
+    if gs.mode == ControlMode::Auto && command.is_gameplay() {
+        ControlMode::stop_auto_mode(gs);
+        return;
+    }
+    match command {
+        Command::Move(delta_x, delta_y) => ControlMode::process_moving(gs, delta_x, delta_y),
+        Command::Action => ControlMode::process_action(gs),
+        Command::SwitchMode => ControlMode::switch_control_mode(gs),
+        Command::Auto => ControlMode::switch_auto_mode(gs),
+        Command::NextCrop => select_next_crop(gs),
+        Command::NextJob => select_next_job(gs),
+        Command::Priority(delta) => change_job_priority(gs, delta),
+        Command::Cancel => ControlMode::process_cancel(gs),
+        Command::GoTo(target) => ControlMode::walk_player(gs, target),
+        Command::Designate(area) => ControlMode::designate(gs, area),
+        Command::TogglePause => gs.world.expect_resource_mut::<GameClock>().toggle_pause(),
+        Command::Step => gs.world.expect_resource_mut::<GameClock>().step(),
+        Command::Speed(speed) => set_game_speed(gs, speed),
+        Command::Save => report_save(gs, save_game(gs, SAVE_PATH), "Game saved"),
+        Command::Load if !gs.input.is_live() => post_message(
+            &gs.world,
+            Severity::Warning,
+            "Loading is disabled while the input is recorded or replayed",
+        ),
+        Command::Load => {
+            let result = load_game(gs, SAVE_PATH);
+            report_save(gs, result, "Game loaded");
+        }
+        Command::Help => gs.is_help_shown = !gs.is_help_shown,
+        Command::ScrollLog(delta) => gs.world.expect_resource_mut::<MessageLog>().scroll(delta),
+        Command::ToggleMinimap => toggle_minimap(gs),
+        Command::ToggleSoil => gs.is_soil_shown = !gs.is_soil_shown,
+        Command::JumpCamera(target) => {
+            gs.minimap_marker = map_to_minimap(target);
+            jump_camera(gs, target);
+        }
+        Command::FreeLook => toggle_free_look(gs),
+        Command::PanCamera(delta_x, delta_y) => pan_camera(gs, Point::new(delta_x, delta_y)),
+        Command::SnapCamera => snap_camera(gs),
+    }
+}
+
+fn select_next_crop(gs: &State) {
+    let mut selected = gs.world.expect_resource_mut::<SelectedCrop>();
+    let crops = gs.world.expect_resource::<Crops>();
+    selected.0 = (selected.0 + 1) % crops.count();
+}
+
+fn select_next_job(gs: &State) {
+    let mut selected = gs.world.expect_resource_mut::<SelectedJob>();
+    selected.kind = selected.kind.next();
+}
+
+fn change_job_priority(gs: &State, delta: i8) {
+    let mut selected = gs.world.expect_resource_mut::<SelectedJob>();
+    selected.priority = selected
+        .priority
+        .saturating_add_signed(delta)
+        .clamp(1, PlanJob::MAX_PRIORITY);
+}
+
+fn set_game_speed(gs: &State, speed: GameSpeed) {
+    gs.world.expect_resource_mut::<GameClock>().set_speed(speed);
+}
+
+/// Shows or hides the minimap, its marker starts under the camera
+fn toggle_minimap(gs: &mut State) {
+    gs.is_minimap_shown = !gs.is_minimap_shown;
+    let camera = *gs.world.expect_resource::<Camera>().get_position();
+    gs.minimap_marker = map_to_minimap(camera);
+}
+
+fn report_save(gs: &State, result: Result<(), SaveError>, success: &str) {
+    match result {
+        Ok(()) => post_message(&gs.world, Severity::Info, success),
+        Err(e) => post_message(&gs.world, Severity::Bad, e.to_string()),
+    }
+}
+
+fn harvest_or_plant(gs: &mut State) {
+    let pos = *gs
+        .world
+        .view::<&Point>()
+        .try_get(gs.player_id)
+        .expect("Player does not exist");
+    if !harvest(&mut gs.world, gs.player_id, pos) {
+        start_future(gs);
     }
 }
 
 fn start_future(gs: &mut State) {
-    let epoch_id = gs.world.epoch();
-    let player_id = gs.player_id.clone();
-    let pos = gs
+    let player_id = gs.player_id;
+    let pos = *gs
         .world
         .view::<&Point>()
         .try_get(player_id)
-        .expect("Player does not exist")
-        .clone();
+        .expect("Player does not exist");
+    let season = gs.world.expect_resource::<Calendar>().season();
+    if !season.is_sowing() {
+        post_message(
+            &gs.world,
+            Severity::Warning,
+            format!("Nothing can be sown in {season}"),
+        );
+        return;
+    }
+    let Some(crop) = crop_to_plant(&gs.world, pos) else {
+        return;
+    };
+    cancel_pending_work(&gs.world, player_id);
+    let handle = start_timer(&gs.world, Some(player_id), 1000f32);
+    gs.world
+        .insert(player_id, PendingWork(handle))
+        .expect("Player does not exist");
     gs.world.spawn_flow_for(player_id, async move |fe| {
-        wait_pause_entity(fe, 1000f32).await;
-        let is_modified = fe.world().map(move |w| {
-            w.view::<Entities>()
-                .modified::<Point>(epoch_id)
-                .try_get(player_id)
-                .is_ok()
-        });
-        if is_modified {
+        // Cancelled when the player walks away
+        if !wait_timer(fe.world(), handle).await {
             return;
         }
-        create_plant_flow(&mut fe.world(), pos);
+        fe.world().map(|w| {
+            create_plant_flow(w, pos, &crop);
+            post_message(w, Severity::Good, format!("Sowed {crop}"));
+        });
     });
 }
+
+fn cancel_pending_work(world: &World, worker: EntityId) {
+    if let Ok(work) = world.view::<&PendingWork>().try_get(worker) {
+        cancel_timer(world, work.0);
+    }
+}
+
+/// Walks the player through the owned planned jobs
+pub fn process_auto_mode(gs: &mut State) {
+    if gs.mode != ControlMode::Auto {
+        return;
+    }
+    let time_delta = gs.world.expect_resource::<GameClock>().delta();
+    work_on_jobs(&mut gs.world, gs.player_id, gs.player_id, time_delta);
+}
@@ -0,0 +1,64 @@
+use std::{error::Error, fs};
+
+use serde::Deserialize;
+
+use crate::ambience::map::MapTile;
+
+pub const CROPS_PATH: &str = "resources/crops.json";
+
+#[derive(Deserialize, Clone)]
+pub struct CropStage {
+    pub glyph: char,
+    pub color: (u8, u8, u8),
+    /// Milliseconds of game time spent in the stage
+    pub duration: f32,
+}
+
+#[derive(Deserialize)]
+pub struct Crop {
+    pub name: String,
+    pub required_tiles: Vec<MapTile>,
+    #[serde(rename = "yield")]
+    pub yield_amount: u32,
+    pub stages: Vec<CropStage>,
+}
+
+impl Crop {
+    pub fn grows_on(&self, tile: MapTile) -> bool {
+        self.required_tiles.contains(&tile)
+    }
+}
+
+/// Crop definitions loaded from the data file at startup
+pub struct Crops {
+    crops: Vec<Crop>,
+}
+
+impl Crops {
+    pub fn get(&self, name: &str) -> Option<&Crop> {
+        self.crops.iter().find(|c| c.name == name)
+    }
+    pub fn stage(&self, name: &str, stage: usize) -> Option<&CropStage> {
+        self.get(name)?.stages.get(stage)
+    }
+    pub fn by_index(&self, index: usize) -> &Crop {
+        &self.crops[index % self.crops.len()]
+    }
+    pub fn count(&self) -> usize {
+        self.crops.len()
+    }
+}
+
+/// Index into `Crops` of the crop the player plants
+pub struct SelectedCrop(pub usize);
+
+pub fn load_crops(path: &str) -> Result<Crops, Box<dyn Error + Send + Sync>> {
+    let crops: Vec<Crop> = serde_json::from_str(&fs::read_to_string(path)?)?;
+    if crops.is_empty() {
+        return Err(format!("{path}: no crops defined").into());
+    }
+    if let Some(crop) = crops.iter().find(|c| c.stages.is_empty()) {
+        return Err(format!("{path}: crop {} has no stages", crop.name).into());
+    }
+    Ok(Crops { crops })
+}
@@ -1,57 +1,176 @@
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, HashMap},
     task::{Context, Poll, Waker},
 };
 
 use edict::{
+    entity::EntityId,
     flow::{FlowEntity, FlowWorld},
     prelude::{Res, ResMut},
+    query::Entities,
     scheduler::Scheduler,
+    view::View,
     world::World,
 };
 
-struct FlowTimerMap {
+use crate::game_clock::GameClock;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct TimerHandle(u64);
+
+struct Timer {
+    release_time: f32,
+    /// Timers of despawned owners are dropped
+    owner: Option<EntityId>,
+    waker: Option<Waker>,
+}
+
+pub struct FlowTimerMap {
     current_time: f32,
-    wakers: BTreeMap<i32, Vec<Waker>>,
+    next_id: u64,
+    timers: HashMap<u64, Timer>,
+    // Entries of cancelled timers are left behind and skipped when due, rescheduled
+    // timers move their entry
+    queue: BTreeMap<i32, Vec<u64>>,
 }
 
 impl FlowTimerMap {
-    fn new() -> Self {
+    fn new(current_time: f32) -> Self {
         FlowTimerMap {
-            current_time: 0f32,
-            wakers: BTreeMap::new(),
+            current_time,
+            next_id: 0,
+            timers: HashMap::new(),
+            queue: BTreeMap::new(),
         }
     }
     fn wake_flows(&mut self, time_delta: f32) {
         self.current_time += time_delta;
         let time = self.current_time as i32;
-        let mut not_ready = self.wakers.split_off(&time);
-        std::mem::swap(&mut self.wakers, &mut not_ready);
-        not_ready.into_values().flatten().for_each(|v| v.wake());
+        let mut not_ready = self.queue.split_off(&time);
+        std::mem::swap(&mut self.queue, &mut not_ready);
+        for id in not_ready.into_values().flatten() {
+            if let Some(waker) = self.timers.get_mut(&id).and_then(|t| t.waker.take()) {
+                waker.wake();
+            }
+        }
+    }
+    fn enqueue(&mut self, id: u64, release_time: f32) {
+        self.queue.entry(release_time as i32).or_default().push(id);
+    }
+    fn start(&mut self, owner: Option<EntityId>, release_time: f32) -> TimerHandle {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.timers.insert(
+            id,
+            Timer {
+                release_time,
+                owner,
+                waker: None,
+            },
+        );
+        self.enqueue(id, release_time);
+        TimerHandle(id)
     }
-    fn add_flow(&mut self, waker: Waker, release_time: i32) {
-        if let Some(v) = self.wakers.get_mut(&release_time) {
-            v.push(waker);
-        } else {
-            self.wakers.insert(release_time, vec![waker]);
+    /// Moves a pending timer to fire at `release_time`, returns `false` if it has already
+    /// fired or been cancelled
+    pub fn reschedule(&mut self, handle: TimerHandle, release_time: f32) -> bool {
+        let Some(timer) = self.timers.get_mut(&handle.0) else {
+            return false;
+        };
+        let old_key = timer.release_time as i32;
+        timer.release_time = release_time;
+        if let Some(ids) = self.queue.get_mut(&old_key) {
+            ids.retain(|id| *id != handle.0);
+            if ids.is_empty() {
+                self.queue.remove(&old_key);
+            }
         }
+        self.enqueue(handle.0, release_time);
+        true
     }
-    fn add_flow_context_f32(&mut self, context: &Context, release_time: f32) {
-        self.add_flow(context.waker().clone(), release_time as i32)
+    /// Returns `false` if the timer has already fired or been cancelled
+    pub fn cancel(&mut self, handle: TimerHandle) -> bool {
+        let Some(timer) = self.timers.remove(&handle.0) else {
+            return false;
+        };
+        // The waiting flow is resumed to find out about the cancellation
+        if let Some(waker) = timer.waker {
+            waker.wake();
+        }
+        true
+    }
+    /// Ready with `true` when the timer fires and with `false` when it was cancelled
+    fn poll_timer(&mut self, handle: TimerHandle, cx: &Context) -> Poll<bool> {
+        let Some(timer) = self.timers.get_mut(&handle.0) else {
+            return Poll::Ready(false);
+        };
+        if timer.release_time > self.current_time {
+            timer.waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        self.timers.remove(&handle.0);
+        Poll::Ready(true)
     }
 }
 
 pub fn init_flow_timers(world: &mut World, scheduler: &mut Scheduler) {
-    world.insert_resource(FlowTimerMap::new());
+    insert_flow_timers(world, 0f32);
     scheduler.add_system(timer);
 }
 
-fn get_current_time(flow_world: &FlowWorld) -> f32 {
-    flow_world.map(|w| w.expect_resource::<FlowTimerMap>().current_time)
+/// Starts the timer clock of a fresh world from `current_time`, used when a save is loaded
+pub fn insert_flow_timers(world: &mut World, current_time: f32) {
+    world.insert_resource(FlowTimerMap::new(current_time));
+}
+
+pub fn current_time(world: &World) -> f32 {
+    world.expect_resource::<FlowTimerMap>().current_time
+}
+
+pub fn get_current_time(flow_world: &FlowWorld) -> f32 {
+    flow_world.map(|w| current_time(w))
 }
 
-fn timer(time_delta: Res<f32>, mut wmap: ResMut<FlowTimerMap>) {
-    wmap.wake_flows(*time_delta);
+fn timer(clock: Res<GameClock>, mut wmap: ResMut<FlowTimerMap>, entities: View<Entities>) {
+    wmap.timers
+        .retain(|_, t| t.owner.is_none_or(|owner| entities.try_get(owner).is_ok()));
+    wmap.wake_flows(clock.delta());
+}
+
+/// Timer firing after `duration`, dropped together with `owner`
+pub fn start_timer(world: &World, owner: Option<EntityId>, duration: f32) -> TimerHandle {
+    let mut wmap = world.expect_resource_mut::<FlowTimerMap>();
+    let release_time = wmap.current_time + duration;
+    wmap.start(owner, release_time)
+}
+
+/// Returns `false` if the timer has already fired or been cancelled
+pub fn cancel_timer(world: &World, handle: TimerHandle) -> bool {
+    world.expect_resource_mut::<FlowTimerMap>().cancel(handle)
+}
+
+/// Moves a pending timer to fire `duration` from now, returns `false` if it has already
+/// fired or been cancelled
+pub fn reschedule_timer(world: &World, handle: TimerHandle, duration: f32) -> bool {
+    let mut wmap = world.expect_resource_mut::<FlowTimerMap>();
+    let release_time = wmap.current_time + duration;
+    wmap.reschedule(handle, release_time)
+}
+
+/// Time left until a pending timer fires, `None` once it has fired or been cancelled
+pub fn remaining_time(world: &World, handle: TimerHandle) -> Option<f32> {
+    let wmap = world.expect_resource::<FlowTimerMap>();
+    let timer = wmap.timers.get(&handle.0)?;
+    Some((timer.release_time - wmap.current_time).max(0.))
+}
+
+/// Resolves to `true` when the timer fires and to `false` when it is cancelled
+pub async fn wait_timer(fw: FlowWorld, handle: TimerHandle) -> bool {
+    fw.poll(move |w, cx| {
+        w.expect_resource_mut::<FlowTimerMap>()
+            .poll_timer(handle, cx)
+    })
+    .await
 }
 
 pub async fn _wait_pause(fw: FlowWorld, pause_time: f32) {
@@ -60,14 +179,11 @@ pub async fn _wait_pause(fw: FlowWorld, pause_time: f32) {
 }
 
 pub async fn _wait_until(fw: FlowWorld, release_time: f32) {
+    let mut handle = None;
     fw.poll(move |w, cx| {
-        let mut wmap = w.get_resource_mut::<FlowTimerMap>().unwrap();
-        if release_time <= wmap.current_time {
-            Poll::Ready(())
-        } else {
-            wmap.add_flow_context_f32(cx, release_time);
-            Poll::Pending
-        }
+        let mut wmap = w.expect_resource_mut::<FlowTimerMap>();
+        let handle = *handle.get_or_insert_with(|| wmap.start(None, release_time));
+        wmap.poll_timer(handle, cx).map(|_| ())
     })
     .await;
 }
@@ -78,14 +194,12 @@ pub async fn wait_pause_entity(fe: FlowEntity, pause_time: f32) {
 }
 
 pub async fn wait_until_entity(fe: FlowEntity, release_time: f32) {
+    let mut handle = None;
     fe.poll(move |mut e, cx| {
-        let mut wmap = e.world().get_resource_mut::<FlowTimerMap>().unwrap();
-        if release_time <= wmap.current_time {
-            Poll::Ready(())
-        } else {
-            wmap.add_flow_context_f32(cx, release_time);
-            Poll::Pending
-        }
+        let owner = e.id();
+        let mut wmap = e.world().expect_resource_mut::<FlowTimerMap>();
+        let handle = *handle.get_or_insert_with(|| wmap.start(Some(owner), release_time));
+        wmap.poll_timer(handle, cx).map(|_| ())
     })
     .await;
 }
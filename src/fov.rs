@@ -0,0 +1,116 @@
+use std::collections::HashSet;
+
+use edict::{
+    prelude::{Component, Res, ResMut},
+    view::View,
+};
+use rltk::{Algorithm2D, BaseMap, Point, field_of_view_set};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ambience::map::{MAP_BORDER, MAP_DIMENSION, MapField, MapRevision, tile_index},
+    components::Player,
+};
+
+/// Tiles seen by the entity, recomputed whenever it moves or the map changes
+#[derive(Component, Serialize, Deserialize)]
+pub struct Viewshed {
+    range: i32,
+    #[serde(skip)]
+    visible: HashSet<Point>,
+    /// Position and map revision the visible tiles were computed from
+    #[serde(skip)]
+    computed_at: Option<(Point, u64)>,
+}
+
+impl Viewshed {
+    pub fn new(range: i32) -> Self {
+        Viewshed {
+            range,
+            visible: HashSet::new(),
+            computed_at: None,
+        }
+    }
+    pub fn is_visible(&self, p: Point) -> bool {
+        self.visible.contains(&p)
+    }
+    pub fn visible(&self) -> impl Iterator<Item = &Point> {
+        self.visible.iter()
+    }
+}
+
+/// Tiles the player has ever seen, drawn from memory when out of sight
+pub struct RevealedTiles {
+    tiles: Vec<bool>,
+}
+
+impl RevealedTiles {
+    pub fn new() -> Self {
+        RevealedTiles {
+            tiles: vec![false; MAP_DIMENSION.0 * MAP_DIMENSION.1],
+        }
+    }
+    pub fn is_revealed(&self, p: Point) -> bool {
+        tile_index(p).is_some_and(|i| self.tiles[i])
+    }
+    /// One character per tile in the order of the flattened `MapField`
+    pub fn to_save(&self) -> String {
+        self.tiles
+            .iter()
+            .map(|&t| if t { '1' } else { '0' })
+            .collect()
+    }
+    /// Saves without the memory of the player restore an unexplored map
+    pub fn from_save(saved: &str) -> Self {
+        let mut revealed = RevealedTiles::new();
+        if saved.len() == revealed.tiles.len() {
+            for (tile, c) in revealed.tiles.iter_mut().zip(saved.chars()) {
+                *tile = c == '1';
+            }
+        }
+        revealed
+    }
+}
+
+/// The map as seen by the shadowcasting of rltk, in coordinates starting at zero
+struct SightMap<'a>(&'a MapField);
+
+impl BaseMap for SightMap<'_> {
+    fn is_opaque(&self, idx: usize) -> bool {
+        let (x, y) = (idx % MAP_DIMENSION.0, idx / MAP_DIMENSION.0);
+        self.0[x][y].properties().blocks_sight
+    }
+}
+
+impl Algorithm2D for SightMap<'_> {
+    fn dimensions(&self) -> Point {
+        Point::new(MAP_DIMENSION.0, MAP_DIMENSION.1)
+    }
+}
+
+pub fn process_viewshed(
+    v: View<(&Point, &mut Viewshed)>,
+    map: Res<MapField>,
+    revision: Res<MapRevision>,
+) {
+    let origin = Point::new(MAP_BORDER.x1, MAP_BORDER.y1);
+    for (p, viewshed) in v {
+        let key = (*p, revision.get());
+        if viewshed.computed_at == Some(key) {
+            continue;
+        }
+        viewshed.computed_at = Some(key);
+        viewshed.visible = field_of_view_set(*p - origin, viewshed.range, &SightMap(&map))
+            .into_iter()
+            .map(|seen| seen + origin)
+            .collect();
+    }
+}
+
+pub fn reveal_seen_tiles(v: View<(&Viewshed, &Player)>, mut revealed: ResMut<RevealedTiles>) {
+    for (viewshed, _) in v {
+        for i in viewshed.visible().filter_map(|p| tile_index(*p)) {
+            revealed.tiles[i] = true;
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Game milliseconds simulated by every tick regardless of the frame rate
+pub const TICK_TIME: f32 = 1000. / 60.;
+/// Slow frames are caught up to this number of ticks, the rest of the lag is dropped
+const MAX_CATCH_UP_TICKS: u32 = 10;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum GameSpeed {
+    Normal,
+    Double,
+    Fast,
+}
+
+impl GameSpeed {
+    fn multiplier(&self) -> f32 {
+        match self {
+            GameSpeed::Normal => 1.,
+            GameSpeed::Double => 2.,
+            GameSpeed::Fast => 5.,
+        }
+    }
+}
+
+/// Turns the frame time into whole simulation ticks of `TICK_TIME`, everything
+/// in the simulation reads its delta instead of the frame time
+pub struct GameClock {
+    speed: GameSpeed,
+    is_paused: bool,
+    is_step_requested: bool,
+    accumulator: f32,
+    ticks_due: u32,
+    tick_count: u64,
+}
+
+impl fmt::Display for GameClock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_paused {
+            write!(f, "Paused")
+        } else {
+            write!(f, "{}x", self.speed.multiplier())
+        }
+    }
+}
+
+impl GameClock {
+    pub fn new() -> Self {
+        GameClock {
+            speed: GameSpeed::Normal,
+            is_paused: false,
+            is_step_requested: false,
+            accumulator: 0.,
+            ticks_due: 0,
+            tick_count: 0,
+        }
+    }
+    pub fn advance(&mut self, frame_time: f32) {
+        if self.is_paused {
+            self.ticks_due = self.is_step_requested as u32;
+            self.is_step_requested = false;
+            return;
+        }
+        self.accumulator += frame_time * self.speed.multiplier();
+        let ticks = (self.accumulator / TICK_TIME) as u32;
+        self.accumulator -= ticks as f32 * TICK_TIME;
+        if ticks > MAX_CATCH_UP_TICKS {
+            self.accumulator = 0.;
+        }
+        self.ticks_due = ticks.min(MAX_CATCH_UP_TICKS);
+    }
+    /// Takes the next due tick of the frame
+    pub fn next_tick(&mut self) -> bool {
+        if self.ticks_due == 0 {
+            return false;
+        }
+        self.ticks_due -= 1;
+        self.tick_count += 1;
+        true
+    }
+    pub fn delta(&self) -> f32 {
+        TICK_TIME
+    }
+    /// Ticks simulated since the game start
+    pub fn tick_count(&self) -> u64 {
+        self.tick_count
+    }
+    pub fn toggle_pause(&mut self) {
+        self.is_paused = !self.is_paused;
+    }
+    pub fn set_speed(&mut self, speed: GameSpeed) {
+        self.speed = speed;
+        self.is_paused = false;
+    }
+    /// Advances the paused game by one frame
+    pub fn step(&mut self) {
+        self.is_step_requested = self.is_paused;
+    }
+}
@@ -0,0 +1,33 @@
+use rltk::{ColorPair, DrawBatch, Point, RGBA, Rect, to_cp437};
+
+use crate::{State, terminal_constants::HELP_Z_ORDER};
+
+use super::layout::MAIN_VIEW_POSITION;
+
+/// Active key bindings over the map, toggled by the `Help` command
+pub fn draw_help(gs: &State, draw_batch: &mut DrawBatch) {
+    if !gs.is_help_shown {
+        return;
+    }
+    let lines: Vec<String> = gs
+        .bindings
+        .help()
+        .into_iter()
+        .map(|(command, keys)| format!("{:<14}{}", command.to_string(), keys.join(", ")))
+        .collect();
+    let width = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0) as i32 + 3;
+    let height = (lines.len() as i32 + 1).min(MAIN_VIEW_POSITION.height() - 1);
+    let panel = Rect::with_size(
+        MAIN_VIEW_POSITION.x1 + 1,
+        MAIN_VIEW_POSITION.y1 + 1,
+        width.min(MAIN_VIEW_POSITION.width() - 2),
+        height,
+    );
+    let color = ColorPair::new(RGBA::named(rltk::WHITE), RGBA::named(rltk::BLACK));
+    draw_batch.fill_region(panel, color, to_cp437(' '));
+    draw_batch.draw_double_box(panel, color);
+    for (y, line) in (panel.y1 + 1..panel.y2).zip(lines) {
+        draw_batch.print_color(Point::new(panel.x1 + 2, y), line, color);
+    }
+    draw_batch.submit(HELP_Z_ORDER).expect("Batch error");
+}
@@ -1,8 +1,18 @@
 use rltk::{DrawBatch, Point};
 
-use crate::{State, terminal_constants::INFO_Z_ORDER};
+use crate::{
+    State,
+    calendar::Calendar,
+    crops::{Crops, SelectedCrop},
+    game_clock::GameClock,
+    inventory::Inventory,
+    jobs::SelectedJob,
+    rng::GameRng,
+    terminal_constants::INFO_Z_ORDER,
+    weather::Weather,
+};
 
-use super::layout::STATIC_GUI;
+use super::{inspector::draw_inspector, layout::STATIC_GUI};
 
 pub fn draw_info(gs: &State, draw_batch: &mut DrawBatch) {
     let pos = gs.world.view::<&Point>();
@@ -11,5 +21,48 @@ pub fn draw_info(gs: &State, draw_batch: &mut DrawBatch) {
     let pos = format!("{} {} ", pos.x, pos.y);
     draw_batch.print(Point::new(1, STATIC_GUI.y1 + 1), pos);
     draw_batch.print(Point::new(1, STATIC_GUI.y1 + 2), &gs.mode);
+    let crops = gs.world.expect_resource::<Crops>();
+    let selected = crops.by_index(gs.world.expect_resource::<SelectedCrop>().0);
+    draw_batch.print(
+        Point::new(1, STATIC_GUI.y1 + 3),
+        format!("Crop: {}", selected.name),
+    );
+    let job = gs.world.expect_resource::<SelectedJob>();
+    let job = format!("Job: {} p{} ", job.kind, job.priority);
+    draw_batch.print(Point::new(1, STATIC_GUI.y1 + 4), job);
+    let clock = gs.world.expect_resource::<GameClock>();
+    draw_batch.print(
+        Point::new(1, STATIC_GUI.y1 + 5),
+        format!("Speed: {clock}   "),
+    );
+    let seed = gs.world.expect_resource::<GameRng>().seed();
+    draw_batch.print(Point::new(1, STATIC_GUI.y1 + 6), format!("Seed: {seed}"));
+    let calendar = gs.world.expect_resource::<Calendar>();
+    draw_batch.print(Point::new(1, STATIC_GUI.y1 + 7), format!("{calendar}   "));
+    let weather = gs.world.expect_resource::<Weather>().kind();
+    let time = format!(
+        "{}, {}, {weather}   ",
+        calendar.time_of_day(),
+        calendar.season()
+    );
+    draw_batch.print(Point::new(1, STATIC_GUI.y1 + 8), time);
+    draw_inventory(gs, draw_batch);
+    draw_inspector(gs, draw_batch);
     draw_batch.submit(INFO_Z_ORDER).expect("Batch error");
 }
+
+fn draw_inventory(gs: &State, draw_batch: &mut DrawBatch) {
+    const COLUMN: i32 = 30;
+    let inventory = gs.world.view::<&Inventory>();
+    let Ok(inventory) = inventory.try_get(gs.player_id) else {
+        return;
+    };
+    draw_batch.print(Point::new(COLUMN, STATIC_GUI.y1 + 1), "Inventory:");
+    let rows = STATIC_GUI.y1 + 2..STATIC_GUI.y2 - 1;
+    for (y, stack) in rows.zip(inventory.stacks()) {
+        draw_batch.print(
+            Point::new(COLUMN, y),
+            format!("{} x{}", stack.item, stack.count),
+        );
+    }
+}
@@ -0,0 +1,173 @@
+use edict::{entity::EntityId, query::Entities};
+use rltk::{ColorPair, DrawBatch, Point, RGBA, Rect, to_cp437};
+
+use crate::{
+    State,
+    ambience::{
+        map::{MapField, map_tile},
+        soil::Soil,
+    },
+    calendar::Calendar,
+    camera::is_shown,
+    components::{HareBrain, Mover, PathFollower, PendingWork, Plant, Renderable},
+    control::ControlMode,
+    crops::Crops,
+    flow_timer::{current_time, remaining_time},
+    fov::RevealedTiles,
+    inventory::Inventory,
+    jobs::PlanJob,
+};
+
+use super::layout::INSPECTOR_PANEL;
+
+/// Describes the tile under the mouse, or else under the cursor or the player
+pub fn draw_inspector(gs: &State, draw_batch: &mut DrawBatch) {
+    let pos = inspected_point(gs);
+    // The info console is not cleared between frames
+    let color = ColorPair::new(RGBA::named(rltk::WHITE), RGBA::named(rltk::BLACK));
+    draw_batch.fill_region(INSPECTOR_PANEL, color, to_cp437(' '));
+    let width = INSPECTOR_PANEL.width() as usize;
+    for (y, line) in (INSPECTOR_PANEL.y1..INSPECTOR_PANEL.y2).zip(describe_tile(gs, pos)) {
+        let line: String = line.chars().take(width).collect();
+        draw_batch.print(Point::new(INSPECTOR_PANEL.x1, y), line);
+    }
+}
+
+fn inspected_point(gs: &State) -> Point {
+    #[cfg(feature = "mouse")]
+    if let Some(hovered) = gs.mouse.hovered() {
+        return hovered;
+    }
+    let cursor = *gs
+        .world
+        .view::<&Rect>()
+        .try_get(gs.cursor_id)
+        .expect("Cursor does not exist");
+    match gs.mode {
+        ControlMode::Cursor => Point::new(cursor.x1, cursor.y1),
+        ControlMode::Corner => Point::new(cursor.x2, cursor.y2),
+        ControlMode::Player | ControlMode::Auto => *gs
+            .world
+            .view::<&Point>()
+            .try_get(gs.player_id)
+            .expect("Player does not exist"),
+    }
+}
+
+fn describe_tile(gs: &State, pos: Point) -> Vec<String> {
+    let Some(tile) = map_tile(&gs.world.expect_resource::<MapField>(), pos) else {
+        return vec![format!("{} {}: outside the map", pos.x, pos.y)];
+    };
+    if !gs.world.expect_resource::<RevealedTiles>().is_revealed(pos) {
+        return vec![format!("{} {}: unexplored", pos.x, pos.y)];
+    }
+    let props = tile.properties();
+    let mut lines = vec![
+        format!("{} {}: {tile:?}", pos.x, pos.y),
+        format!(
+            "cost {}{}{}{}",
+            props.move_cost,
+            if props.walkable { "" } else { ", not walkable" },
+            if props.blocks_sight {
+                ", blocks sight"
+            } else {
+                ""
+            },
+            if props.fertile { ", fertile" } else { "" },
+        ),
+    ];
+    if props.fertile
+        && let Some(soil) = gs.world.expect_resource::<Soil>().get(pos)
+    {
+        lines.push(format!(
+            "moisture {}%, fertility {}%{}",
+            (soil.moisture * 100.) as i32,
+            (soil.fertility * 100.) as i32,
+            if soil.is_tilled { ", tilled" } else { "" },
+        ));
+    }
+    let entities: Vec<EntityId> = gs
+        .world
+        .view::<(Entities, &Point, Option<&Mover>)>()
+        .iter()
+        .filter(|(e, p, mover)| **p == pos && is_shown(gs, e.id(), pos, mover.is_some()))
+        .map(|(e, ..)| e.id())
+        .collect();
+    for id in entities {
+        lines.push(describe_entity(gs, id));
+    }
+    lines
+}
+
+fn describe_entity(gs: &State, id: EntityId) -> String {
+    let world = &gs.world;
+    let mut parts = Vec::new();
+    if id == gs.player_id {
+        parts.push("Player".to_string());
+    }
+    if let Ok(brain) = world.view::<&HareBrain>().try_get(id) {
+        if brain.is_resting(world.expect_resource::<Calendar>().phase()) {
+            parts.push("Hare resting".to_string());
+        } else {
+            parts.push(format!("Hare heading {}", brain.heading()));
+        }
+    }
+    if let Ok(plant) = world.view::<&Plant>().try_get(id) {
+        parts.push(describe_plant(gs, plant));
+    }
+    if let Ok(job) = world.view::<&PlanJob>().try_get(id) {
+        let progress = (job.progress() * 100.) as i32;
+        let reserved = if job.is_reserved() { ", reserved" } else { "" };
+        parts.push(format!(
+            "Job {} p{} {progress}%{reserved}",
+            job.kind, job.priority
+        ));
+    }
+    if let Ok(work) = world.view::<&PendingWork>().try_get(id)
+        && let Some(remaining) = remaining_time(world, work.0)
+    {
+        parts.push(format!("working {:.1}s left", remaining / 1000.));
+    }
+    if let Ok(mover) = world.view::<&Mover>().try_get(id)
+        && let Some(direction) = mover.direction_name()
+    {
+        parts.push(format!("moving {direction}"));
+    }
+    if let Ok(follower) = world.view::<&PathFollower>().try_get(id)
+        && let Some(target) = follower.target()
+    {
+        parts.push(format!("to {} {}", target.x, target.y));
+    }
+    if let Ok(inventory) = world.view::<&Inventory>().try_get(id) {
+        let count: u32 = inventory.stacks().iter().map(|s| s.count).sum();
+        parts.push(format!("carries {count}"));
+    }
+    if parts.is_empty() {
+        let glyph = world
+            .view::<&Renderable>()
+            .try_get(id)
+            .ok()
+            .and_then(|r| char::from_u32(r.glyph as u32))
+            .unwrap_or('?');
+        parts.push(format!("Object {glyph}"));
+    }
+    parts.join(", ")
+}
+
+fn describe_plant(gs: &State, plant: &Plant) -> String {
+    let crops = gs.world.expect_resource::<Crops>();
+    let stages = crops.get(plant.crop()).map_or(0, |c| c.stages.len());
+    // Ripe plants are past the last stage of the crop
+    let shown_stage = plant.stage().min(stages.saturating_sub(1)) + 1;
+    let stage = format!("{} {shown_stage}/{stages}", plant.crop());
+    if plant.is_ripe(&crops) {
+        return format!("{stage} ripe");
+    }
+    match plant.stage_end(&crops) {
+        Some(end) => {
+            let remaining = (end - current_time(&gs.world)).max(0.) / 1000.;
+            format!("{stage} {remaining:.1}s left")
+        }
+        None => stage,
+    }
+}
@@ -1,5 +1,7 @@
 use rltk::{Point, Rect};
 
+use crate::ambience::map::MAP_DIMENSION;
+
 #[cfg(feature = "default")]
 pub const MAIN_CONSOLE_DIMENSION: Point = Point::constant(100, 50);
 #[cfg(feature = "default")]
@@ -19,6 +21,28 @@ pub const STATIC_GUI: Rect = Rect {
     y2: ADDITIONAL_CONSOLE_DIMENSION.y,
 };
 
+/// Map tiles per minimap cell along each axis
+#[cfg(feature = "default")]
+pub const MINIMAP_SCALE: i32 = 6;
+
+/// Message log inside `STATIC_GUI`, right of the inspector
+#[cfg(feature = "default")]
+pub const LOG_PANEL: Rect = Rect {
+    x1: 130,
+    x2: ADDITIONAL_CONSOLE_DIMENSION.x - 1,
+    y1: STATIC_GUI.y1 + 1,
+    y2: STATIC_GUI.y2 - 1,
+};
+
+/// Tile inspector inside `STATIC_GUI`, ending where the log starts
+#[cfg(feature = "default")]
+pub const INSPECTOR_PANEL: Rect = Rect {
+    x1: 60,
+    x2: LOG_PANEL.x1 - 1,
+    y1: STATIC_GUI.y1 + 1,
+    y2: STATIC_GUI.y2 - 1,
+};
+
 #[cfg(feature = "tablet")]
 pub const MAIN_CONSOLE_DIMENSION: Point = Point::constant(120, 40);
 #[cfg(feature = "tablet")]
@@ -31,3 +55,37 @@ pub const MAIN_VIEW_POSITION: Rect = Rect {
     y1: 3,
     y2: 25,
 };
+
+/// Map tiles per minimap cell along each axis
+#[cfg(feature = "tablet")]
+pub const MINIMAP_SCALE: i32 = 10;
+
+/// Message log inside `STATIC_GUI`, below the inspector
+#[cfg(feature = "tablet")]
+pub const LOG_PANEL: Rect = Rect {
+    x1: 60,
+    x2: ADDITIONAL_CONSOLE_DIMENSION.x - 1,
+    y1: STATIC_GUI.y1 + 8,
+    y2: STATIC_GUI.y2 - 1,
+};
+
+/// Tile inspector inside `STATIC_GUI`, above the log
+#[cfg(feature = "tablet")]
+pub const INSPECTOR_PANEL: Rect = Rect {
+    x1: LOG_PANEL.x1,
+    x2: ADDITIONAL_CONSOLE_DIMENSION.x - 1,
+    y1: STATIC_GUI.y1 + 1,
+    y2: LOG_PANEL.y1,
+};
+
+/// Minimap overlay in the top right corner of the main view
+pub const MINIMAP_POSITION: Rect = Rect {
+    x1: MAIN_VIEW_POSITION.x2 - minimap_size(MAP_DIMENSION.0),
+    x2: MAIN_VIEW_POSITION.x2,
+    y1: MAIN_VIEW_POSITION.y1,
+    y2: MAIN_VIEW_POSITION.y1 + minimap_size(MAP_DIMENSION.1),
+};
+
+const fn minimap_size(map_size: usize) -> i32 {
+    map_size.div_ceil(MINIMAP_SCALE as usize) as i32
+}
@@ -0,0 +1,29 @@
+use rltk::{ColorPair, DrawBatch, Point, RGBA, to_cp437};
+
+use crate::{State, message_log::MessageLog, terminal_constants::INFO_Z_ORDER};
+
+use super::layout::LOG_PANEL;
+
+pub fn draw_log(gs: &State, draw_batch: &mut DrawBatch) {
+    let background = RGBA::named(rltk::BLACK);
+    draw_batch.fill_region(
+        LOG_PANEL,
+        ColorPair::new(RGBA::named(rltk::WHITE), background),
+        to_cp437(' '),
+    );
+    let log = gs.world.expect_resource::<MessageLog>();
+    let width = LOG_PANEL.width() as usize;
+    let rows = LOG_PANEL.height() as usize;
+    for (y, message) in (LOG_PANEL.y1..).zip(log.visible(rows)) {
+        let seconds = (message.time / 1000.) as u32;
+        let line = format!("{:02}:{:02} {}", seconds / 60, seconds % 60, message.text);
+        let line: String = line.chars().take(width).collect();
+        let color = ColorPair::new(message.severity.color(), background);
+        draw_batch.print_color(Point::new(LOG_PANEL.x1, y), line, color);
+    }
+    if log.is_scrolled() {
+        let marker = ColorPair::new(RGBA::named(rltk::YELLOW), background);
+        draw_batch.print_color(Point::new(LOG_PANEL.x2 - 1, LOG_PANEL.y2 - 1), "↓", marker);
+    }
+    draw_batch.submit(INFO_Z_ORDER + 1).expect("Batch error");
+}
@@ -0,0 +1,133 @@
+use edict::query::Entities;
+use rltk::{ColorPair, DrawBatch, Point, RGBA, Rect};
+
+use crate::{
+    State,
+    ambience::map::{MAP_BORDER, MapField, MapTile},
+    camera::is_shown,
+    components::{HareBrain, Plant},
+    fov::RevealedTiles,
+    jobs::PlanJob,
+    terminal_constants::HELP_Z_ORDER,
+};
+
+use super::layout::{MAIN_VIEW_POSITION, MINIMAP_POSITION, MINIMAP_SCALE};
+
+/// The whole map over the main view, one cell per `MINIMAP_SCALE` square of tiles
+pub fn draw_minimap(gs: &State, draw_batch: &mut DrawBatch) {
+    if !gs.is_minimap_shown {
+        return;
+    }
+    let map = gs.world.expect_resource::<MapField>();
+    let revealed = gs.world.expect_resource::<RevealedTiles>();
+    MINIMAP_POSITION.for_each(|cell| {
+        let color = if is_explored(&revealed, cell) {
+            tile_color(dominant_tile(&map, cell))
+        } else {
+            RGBA::named(rltk::BLACK)
+        };
+        draw_batch.set(cell, ColorPair::new(color, color), ' ');
+    });
+
+    let fields = ColorPair::new(
+        RGBA::named(rltk::YELLOW_GREEN),
+        RGBA::named(rltk::CADET_BLUE),
+    );
+    // Every layer hides what the player has not seen, as the main view does
+    for (e, pos, _) in gs.world.view::<(Entities, &Point, &PlanJob)>().iter() {
+        if is_shown(gs, e.id(), *pos, false) {
+            draw_batch.set_bg(map_to_minimap(*pos), fields.bg);
+        }
+    }
+    for (e, pos, _) in gs.world.view::<(Entities, &Point, &Plant)>().iter() {
+        if is_shown(gs, e.id(), *pos, false) {
+            draw_batch.set(map_to_minimap(*pos), fields, '"');
+        }
+    }
+    let hare = ColorPair::new(RGBA::named(rltk::RED3), RGBA::named(rltk::BLACK));
+    for (e, pos, _) in gs.world.view::<(Entities, &Point, &HareBrain)>().iter() {
+        if is_shown(gs, e.id(), *pos, true) {
+            draw_batch.set(map_to_minimap(*pos), hare, 'h');
+        }
+    }
+
+    let start_view = gs.get_camera_start_view();
+    let top_left =
+        map_to_minimap(start_view + Point::new(MAIN_VIEW_POSITION.x1, MAIN_VIEW_POSITION.y1));
+    let bottom_right = map_to_minimap(
+        start_view + Point::new(MAIN_VIEW_POSITION.x2 - 1, MAIN_VIEW_POSITION.y2 - 1),
+    );
+    let viewport = Rect::with_exact(top_left.x, top_left.y, bottom_right.x, bottom_right.y);
+    let frame = ColorPair::new(RGBA::named(rltk::WHITE), RGBA::named(rltk::BLACK));
+    draw_batch.draw_hollow_box(viewport, frame);
+    let marker = ColorPair::new(RGBA::named(rltk::YELLOW), RGBA::named(rltk::BLACK));
+    draw_batch.set(gs.minimap_marker, marker, '+');
+
+    let player = *gs
+        .world
+        .view::<&Point>()
+        .try_get(gs.player_id)
+        .expect("Player does not exist");
+    let player_color = ColorPair::new(RGBA::named(rltk::RED), RGBA::named(rltk::BLACK));
+    draw_batch.set(map_to_minimap(player), player_color, 'Ӂ');
+    draw_batch.submit(HELP_Z_ORDER - 1).expect("Batch error");
+}
+
+/// Map cell in the middle of the tiles shown by the minimap `cell`
+pub fn minimap_to_map(cell: Point) -> Point {
+    let x = cell.x.clamp(MINIMAP_POSITION.x1, MINIMAP_POSITION.x2 - 1) - MINIMAP_POSITION.x1;
+    let y = cell.y.clamp(MINIMAP_POSITION.y1, MINIMAP_POSITION.y2 - 1) - MINIMAP_POSITION.y1;
+    Point::new(
+        MAP_BORDER.x1 + x * MINIMAP_SCALE + MINIMAP_SCALE / 2,
+        MAP_BORDER.y1 + y * MINIMAP_SCALE + MINIMAP_SCALE / 2,
+    )
+}
+
+pub fn map_to_minimap(pos: Point) -> Point {
+    Point::new(
+        MINIMAP_POSITION.x1 + (pos.x - MAP_BORDER.x1).div_euclid(MINIMAP_SCALE),
+        MINIMAP_POSITION.y1 + (pos.y - MAP_BORDER.y1).div_euclid(MINIMAP_SCALE),
+    )
+}
+
+fn is_explored(revealed: &RevealedTiles, cell: Point) -> bool {
+    let top_left = minimap_to_map(cell) - Point::new(MINIMAP_SCALE / 2, MINIMAP_SCALE / 2);
+    (0..MINIMAP_SCALE)
+        .flat_map(|x| (0..MINIMAP_SCALE).map(move |y| top_left + Point::new(x, y)))
+        .any(|p| revealed.is_revealed(p))
+}
+
+/// Most common tile of the square shown by the minimap `cell`
+fn dominant_tile(map: &MapField, cell: Point) -> MapTile {
+    const TILES: [MapTile; 6] = [
+        MapTile::Ground,
+        MapTile::Water,
+        MapTile::Grass,
+        MapTile::Forest,
+        MapTile::Ice,
+        MapTile::Fence,
+    ];
+    let mut counts = [0; TILES.len()];
+    let x0 = ((cell.x - MINIMAP_POSITION.x1) * MINIMAP_SCALE) as usize;
+    let y0 = ((cell.y - MINIMAP_POSITION.y1) * MINIMAP_SCALE) as usize;
+    for column in map.iter().skip(x0).take(MINIMAP_SCALE as usize) {
+        for tile in column.iter().skip(y0).take(MINIMAP_SCALE as usize) {
+            let i = TILES.iter().position(|t| t == tile).unwrap();
+            counts[i] += 1;
+        }
+    }
+    let max = counts.iter().max().copied().unwrap_or(0);
+    let i = counts.iter().position(|c| *c == max).unwrap();
+    TILES[i]
+}
+
+fn tile_color(tile: MapTile) -> RGBA {
+    match tile {
+        MapTile::Ground => RGBA::named(rltk::SANDY_BROWN),
+        MapTile::Water => RGBA::named(rltk::BLUE),
+        MapTile::Grass => RGBA::named(rltk::LIGHT_GREEN),
+        MapTile::Forest => RGBA::named(rltk::DARK_GREEN),
+        MapTile::Ice => RGBA::named(rltk::LIGHT_STEEL_BLUE),
+        MapTile::Fence => RGBA::named(rltk::SADDLE_BROWN),
+    }
+}
@@ -1,4 +1,8 @@
+pub mod help;
 pub mod hud;
 pub mod info;
+pub mod inspector;
 pub mod layout;
+pub mod log;
+pub mod minimap;
 pub mod static_gui;
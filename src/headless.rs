@@ -0,0 +1,54 @@
+use edict::world::World;
+use rltk::{Point, VirtualKeyCode};
+
+use crate::{
+    State,
+    components::{HareBrain, Plant},
+    game_clock::{GameClock, TICK_TIME},
+};
+
+/// The game without a window: the same world, systems and flows as the windowed game,
+/// advanced tick by tick and fed with scripted keys instead of a context
+pub struct Headless {
+    pub state: State,
+    frame: u64,
+}
+
+impl Headless {
+    pub fn new(state: State) -> Self {
+        Headless { state, frame: 0 }
+    }
+    /// Runs `frames` frames of one tick each, `script` holds the keys pressed
+    /// on the given frame counted from the game start
+    pub fn run(&mut self, frames: u64, script: &[(u64, VirtualKeyCode)]) {
+        for _ in 0..frames {
+            let command = script
+                .iter()
+                .find(|(frame, _)| *frame == self.frame)
+                .and_then(|(_, key)| self.state.bindings.command(*key));
+            self.state.update(TICK_TIME, command);
+            self.frame += 1;
+        }
+    }
+    pub fn world(&self) -> &World {
+        &self.state.world
+    }
+    pub fn summary(&self) -> String {
+        let world = self.world();
+        let ticks = world.expect_resource::<GameClock>().tick_count();
+        let player = *world
+            .view::<&Point>()
+            .try_get(self.state.player_id)
+            .expect("Player does not exist");
+        let hares: Vec<Point> = world
+            .view::<(&Point, &HareBrain)>()
+            .iter()
+            .map(|(p, _)| *p)
+            .collect();
+        let plants = world.view::<&Plant>().iter().count();
+        format!(
+            "ticks: {ticks}, player: {} {}, hares: {hares:?}, plants: {plants}",
+            player.x, player.y
+        )
+    }
+}
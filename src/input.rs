@@ -0,0 +1,298 @@
+use std::{
+    error::Error,
+    fmt,
+    fs::{self, File},
+    io::{self, LineWriter, Write},
+    path::Path,
+};
+
+use rltk::{Point, Rect, VirtualKeyCode};
+use serde::{Deserialize, Serialize};
+
+use crate::{ambience::generator::MapPreset, arg_value, game_clock::GameSpeed};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Command {
+    Move(i32, i32),
+    Action,
+    SwitchMode,
+    Auto,
+    NextCrop,
+    NextJob,
+    Priority(i8),
+    Cancel,
+    TogglePause,
+    Step,
+    Speed(GameSpeed),
+    Save,
+    Load,
+    Help,
+    /// Walk the player to the map cell
+    GoTo(Point),
+    /// Set the cursor rectangle in map cells
+    Designate(Rect),
+    /// Positive values scroll the message log back
+    ScrollLog(i8),
+    ToggleMinimap,
+    /// Show the moisture, fertility and tillage of the soil instead of the terrain
+    ToggleSoil,
+    /// Center the camera on the map cell
+    JumpCamera(Point),
+    FreeLook,
+    /// Pan the camera by map cells
+    PanCamera(i32, i32),
+    /// Center the camera on the player and follow it again
+    SnapCamera,
+}
+
+impl Command {
+    /// Commands changing the simulated world, only they are recorded and replayed.
+    /// The clock, the save files and the panels stay in the hands of whoever watches the game
+    pub fn is_gameplay(&self) -> bool {
+        !matches!(
+            self,
+            Command::TogglePause
+                | Command::Step
+                | Command::Speed(_)
+                | Command::Save
+                | Command::Load
+                | Command::Help
+                | Command::ScrollLog(_)
+                | Command::ToggleMinimap
+                | Command::ToggleSoil
+                | Command::JumpCamera(_)
+                | Command::FreeLook
+                | Command::PanCamera(..)
+                | Command::SnapCamera
+        )
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Command::Move(delta_x, delta_y) => write!(f, "Move {delta_x:+} {delta_y:+}"),
+            Command::Priority(delta) => write!(f, "Priority {delta:+}"),
+            Command::ScrollLog(delta) => write!(f, "Scroll log {delta:+}"),
+            Command::PanCamera(delta_x, delta_y) => write!(f, "Pan {delta_x:+} {delta_y:+}"),
+            Command::Speed(speed) => write!(f, "Speed {speed:?}"),
+            _ => fmt::Debug::fmt(self, f),
+        }
+    }
+}
+
+/// Bindings loaded instead of the defaults when present
+pub const KEYS_PATH: &str = "keys.json";
+
+const DEFAULT_BINDINGS: &[(VirtualKeyCode, Command)] = &[
+    (VirtualKeyCode::Left, Command::Move(-1, 0)),
+    (VirtualKeyCode::Right, Command::Move(1, 0)),
+    (VirtualKeyCode::Up, Command::Move(0, -1)),
+    (VirtualKeyCode::Down, Command::Move(0, 1)),
+    (VirtualKeyCode::Return, Command::Action),
+    (VirtualKeyCode::P, Command::SwitchMode),
+    (VirtualKeyCode::A, Command::Auto),
+    (VirtualKeyCode::C, Command::NextCrop),
+    (VirtualKeyCode::J, Command::NextJob),
+    (VirtualKeyCode::PageUp, Command::Priority(1)),
+    (VirtualKeyCode::PageDown, Command::Priority(-1)),
+    (VirtualKeyCode::Delete, Command::Cancel),
+    (VirtualKeyCode::Space, Command::TogglePause),
+    (VirtualKeyCode::Period, Command::Step),
+    (VirtualKeyCode::Key1, Command::Speed(GameSpeed::Normal)),
+    (VirtualKeyCode::Key2, Command::Speed(GameSpeed::Double)),
+    (VirtualKeyCode::Key3, Command::Speed(GameSpeed::Fast)),
+    (VirtualKeyCode::F5, Command::Save),
+    (VirtualKeyCode::F9, Command::Load),
+    (VirtualKeyCode::F1, Command::Help),
+    (VirtualKeyCode::Home, Command::ScrollLog(1)),
+    (VirtualKeyCode::End, Command::ScrollLog(-1)),
+    (VirtualKeyCode::M, Command::ToggleMinimap),
+    (VirtualKeyCode::S, Command::ToggleSoil),
+    (VirtualKeyCode::F, Command::FreeLook),
+    (VirtualKeyCode::Back, Command::SnapCamera),
+];
+
+macro_rules! key_names {
+    ($($key:ident),* $(,)?) => {
+        &[$((stringify!($key), VirtualKeyCode::$key)),*]
+    };
+}
+
+/// Keys that can be bound in the bindings file, named after their `VirtualKeyCode`
+const KEY_NAMES: &[(&str, VirtualKeyCode)] = key_names![
+    A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z, Key0, Key1, Key2,
+    Key3, Key4, Key5, Key6, Key7, Key8, Key9, Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5,
+    Numpad6, Numpad7, Numpad8, Numpad9, F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12, Left,
+    Right, Up, Down, Home, End, PageUp, PageDown, Insert, Delete, Return, Space, Escape, Tab, Back,
+    Period, Comma, Slash, Minus, Equals, Semicolon,
+];
+
+fn key_by_name(name: &str) -> Option<VirtualKeyCode> {
+    KEY_NAMES
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, key)| *key)
+}
+
+fn key_name(key: VirtualKeyCode) -> &'static str {
+    KEY_NAMES
+        .iter()
+        .find(|(_, k)| *k == key)
+        .map_or("?", |(name, _)| *name)
+}
+
+#[derive(Deserialize)]
+struct BindingEntry {
+    keys: Vec<String>,
+    command: Command,
+}
+
+pub struct KeyBindings {
+    bindings: Vec<(VirtualKeyCode, Command)>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings {
+            bindings: DEFAULT_BINDINGS.to_vec(),
+        }
+    }
+}
+
+impl KeyBindings {
+    /// Bindings of the file at `path`, a list of `{"keys": [..], "command": ..}` entries
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let entries: Vec<BindingEntry> = serde_json::from_str(&fs::read_to_string(path)?)?;
+        let mut bindings: Vec<(VirtualKeyCode, Command)> = Vec::new();
+        for entry in entries {
+            for name in &entry.keys {
+                let key = key_by_name(name).ok_or_else(|| format!("{path}: unknown key {name}"))?;
+                if bindings.iter().any(|(k, _)| *k == key) {
+                    return Err(format!("{path}: key {name} is bound twice").into());
+                }
+                bindings.push((key, entry.command));
+            }
+        }
+        Ok(KeyBindings { bindings })
+    }
+    /// Bindings of `--keys <path>`, of `KEYS_PATH` if it exists, the defaults otherwise
+    pub fn from_args() -> Result<Self, Box<dyn Error + Send + Sync>> {
+        match arg_value("--keys") {
+            Some(path) => Self::load(&path),
+            None if Path::new(KEYS_PATH).exists() => Self::load(KEYS_PATH),
+            None => Ok(Self::default()),
+        }
+    }
+    pub fn command(&self, key: VirtualKeyCode) -> Option<Command> {
+        self.bindings
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, command)| *command)
+    }
+    /// Bound commands in the order of the bindings, each with the names of its keys
+    pub fn help(&self) -> Vec<(Command, Vec<&'static str>)> {
+        let mut help: Vec<(Command, Vec<&'static str>)> = Vec::new();
+        for (key, command) in &self.bindings {
+            match help.iter_mut().find(|(c, _)| c == command) {
+                Some((_, keys)) => keys.push(key_name(*key)),
+                None => help.push((*command, vec![key_name(*key)])),
+            }
+        }
+        help
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TimedCommand {
+    /// Applied before this tick of the game clock is simulated
+    pub tick: u64,
+    pub command: Command,
+}
+
+/// First line of a recording, followed by a `TimedCommand` per line
+#[derive(Serialize, Deserialize)]
+struct LogHeader {
+    seed: u64,
+    /// Recordings made before the presets were selectable are on the noise map
+    #[serde(default)]
+    map: MapPreset,
+}
+
+/// Gameplay commands of a session, replayed on a new game with the same seed
+pub struct InputLog {
+    pub seed: u64,
+    pub map: MapPreset,
+    pub commands: Vec<TimedCommand>,
+}
+
+impl InputLog {
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let text = fs::read_to_string(path)?;
+        let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+        let header = lines
+            .next()
+            .ok_or_else(|| format!("{path}: empty recording"))?;
+        let header: LogHeader = serde_json::from_str(header)?;
+        let commands = lines
+            .map(|line| serde_json::from_str(line))
+            .collect::<Result<_, _>>()?;
+        Ok(InputLog {
+            seed: header.seed,
+            map: header.map,
+            commands,
+        })
+    }
+}
+
+fn write_line(file: &mut impl Write, value: &impl Serialize) -> io::Result<()> {
+    serde_json::to_writer(&mut *file, value)?;
+    file.write_all(b"\n")
+}
+
+/// Where the gameplay commands come from
+pub enum Input {
+    Live,
+    /// Live commands, appended to the file line by line to survive a crash
+    Recording(LineWriter<File>),
+    /// Commands of the log, the gameplay keys of the player are ignored
+    Replay {
+        log: InputLog,
+        next: usize,
+    },
+}
+
+impl Input {
+    pub fn record(seed: u64, map: MapPreset, path: &str) -> io::Result<Self> {
+        let mut file = LineWriter::new(File::create(path)?);
+        write_line(&mut file, &LogHeader { seed, map })?;
+        Ok(Input::Recording(file))
+    }
+    pub fn replay(log: InputLog) -> Self {
+        Input::Replay { log, next: 0 }
+    }
+    /// Whether the world may be replaced by a save without breaking the log
+    pub fn is_live(&self) -> bool {
+        matches!(self, Input::Live)
+    }
+    /// Takes the gameplay command of the player given at `tick`, returns whether to apply it
+    pub fn accept(&mut self, command: Command, tick: u64) -> io::Result<bool> {
+        match self {
+            Input::Live => Ok(true),
+            Input::Recording(file) => {
+                write_line(file, &TimedCommand { tick, command })?;
+                Ok(true)
+            }
+            Input::Replay { .. } => Ok(false),
+        }
+    }
+    /// Next replayed command to apply before `tick` is simulated
+    pub fn due_command(&mut self, tick: u64) -> Option<Command> {
+        let Input::Replay { log, next } = self else {
+            return None;
+        };
+        let timed = log.commands.get(*next).filter(|c| c.tick <= tick)?;
+        *next += 1;
+        Some(timed.command)
+    }
+}
@@ -0,0 +1,31 @@
+use edict::prelude::Component;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+pub struct ItemStack {
+    pub item: String,
+    pub count: u32,
+}
+
+#[derive(Component, Serialize, Deserialize)]
+pub struct Inventory {
+    stacks: Vec<ItemStack>,
+}
+
+impl Inventory {
+    pub fn new() -> Self {
+        Inventory { stacks: Vec::new() }
+    }
+    pub fn add(&mut self, item: &str, count: u32) {
+        match self.stacks.iter_mut().find(|s| s.item == item) {
+            Some(stack) => stack.count += count,
+            None => self.stacks.push(ItemStack {
+                item: item.to_string(),
+                count,
+            }),
+        }
+    }
+    pub fn stacks(&self) -> &[ItemStack] {
+        &self.stacks
+    }
+}
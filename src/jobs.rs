@@ -0,0 +1,352 @@
+use std::{cmp::Reverse, fmt};
+
+use edict::{
+    entity::EntityId,
+    prelude::{ChildOf, Component},
+    query::Entities,
+    world::World,
+};
+use rltk::{Point, Rect};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ambience::{
+        map::{MapField, MapRevision, MapTile, map_tile, set_map_tile},
+        path::find_path,
+        soil::Soil,
+    },
+    calendar::Calendar,
+    components::{Mover, PathFollower, Plant, harvest},
+    crops::{Crops, SelectedCrop},
+    spawn::create_plant_flow,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum JobKind {
+    Till,
+    Sow,
+    Water,
+    Harvest,
+    Build,
+    Clear,
+}
+
+impl fmt::Display for JobKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+impl JobKind {
+    const ALL: [JobKind; 6] = [
+        JobKind::Till,
+        JobKind::Sow,
+        JobKind::Water,
+        JobKind::Harvest,
+        JobKind::Build,
+        JobKind::Clear,
+    ];
+
+    pub fn next(&self) -> JobKind {
+        let i = Self::ALL.iter().position(|k| k == self).unwrap();
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+    /// Milliseconds of work needed on the spot
+    fn work_time(&self) -> f32 {
+        match self {
+            JobKind::Till => 1500.,
+            JobKind::Sow => 1000.,
+            JobKind::Water => 800.,
+            JobKind::Harvest => 1000.,
+            JobKind::Build => 5000.,
+            JobKind::Clear => 2000.,
+        }
+    }
+    pub fn glyph(&self) -> char {
+        match self {
+            JobKind::Till => '=',
+            JobKind::Sow => '.',
+            JobKind::Water => '~',
+            JobKind::Harvest => 'T',
+            JobKind::Build => '#',
+            JobKind::Clear => 'x',
+        }
+    }
+}
+
+#[derive(Component, Serialize, Deserialize)]
+pub struct PlanJob {
+    pub kind: JobKind,
+    pub priority: u8,
+    /// Milliseconds of work already done
+    progress: f32,
+    #[serde(skip)]
+    reserved_by: Option<EntityId>,
+}
+
+impl PlanJob {
+    pub const MAX_PRIORITY: u8 = 5;
+
+    pub fn new(kind: JobKind, priority: u8) -> Self {
+        PlanJob {
+            kind,
+            priority,
+            progress: 0.,
+            reserved_by: None,
+        }
+    }
+    /// Done part of the work from 0 to 1
+    pub fn progress(&self) -> f32 {
+        self.progress / self.kind.work_time()
+    }
+    pub fn is_reserved(&self) -> bool {
+        self.reserved_by.is_some()
+    }
+}
+
+/// Kind and priority given to the jobs designated with the cursor
+pub struct SelectedJob {
+    pub kind: JobKind,
+    pub priority: u8,
+}
+
+pub enum JobOutcome {
+    Completed,
+    Failed(&'static str),
+    Cancelled,
+}
+
+pub struct JobEvent {
+    pub kind: JobKind,
+    pub pos: Point,
+    pub outcome: JobOutcome,
+}
+
+impl fmt::Display for JobEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at {} {}: ", self.kind, self.pos.x, self.pos.y)?;
+        match self.outcome {
+            JobOutcome::Completed => write!(f, "completed"),
+            JobOutcome::Failed(reason) => write!(f, "failed, {reason}"),
+            JobOutcome::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+/// Finished jobs since the last drain
+#[derive(Default)]
+pub struct JobEvents {
+    events: Vec<JobEvent>,
+}
+
+impl JobEvents {
+    pub fn drain(&mut self) -> impl Iterator<Item = JobEvent> + '_ {
+        self.events.drain(..)
+    }
+}
+
+fn finish_job(world: &mut World, job_id: EntityId, kind: JobKind, pos: Point, outcome: JobOutcome) {
+    world
+        .expect_resource_mut::<JobEvents>()
+        .events
+        .push(JobEvent { kind, pos, outcome });
+    let _ = world.despawn(job_id);
+}
+
+pub fn cancel_jobs(world: &mut World, owner: EntityId, area: Rect) {
+    let jobs: Vec<(EntityId, Point, JobKind)> = world
+        .view::<(Entities, &Point, &PlanJob)>()
+        .filter_relates_to::<ChildOf>(owner)
+        .iter()
+        .filter(|(_, p, _)| area.point_in_rect(**p))
+        .map(|(e, p, job)| (e.id(), *p, job.kind))
+        .collect();
+    for (job_id, pos, kind) in jobs {
+        finish_job(world, job_id, kind, pos, JobOutcome::Cancelled);
+    }
+}
+
+/// Fails the jobs of every owner on the tiles where `is_lost` holds
+pub fn fail_jobs(world: &mut World, is_lost: impl Fn(Point) -> bool, reason: &'static str) {
+    let jobs: Vec<(EntityId, Point, JobKind)> = world
+        .view::<(Entities, &Point, &PlanJob)>()
+        .iter()
+        .filter(|(_, p, _)| is_lost(**p))
+        .map(|(e, p, job)| (e.id(), *p, job.kind))
+        .collect();
+    for (job_id, pos, kind) in jobs {
+        finish_job(world, job_id, kind, pos, JobOutcome::Failed(reason));
+    }
+}
+
+pub fn has_job_at(world: &World, owner: EntityId, pos: Point) -> bool {
+    world
+        .view::<(&Point, &PlanJob)>()
+        .filter_relates_to::<ChildOf>(owner)
+        .iter()
+        .any(|(p, _)| *p == pos)
+}
+
+/// Drives `worker` through the jobs designated by `owner`: keeps the reserved job
+/// or reserves the most urgent one, walks there and spends `time_delta` working on it
+pub fn work_on_jobs(world: &mut World, worker: EntityId, owner: EntityId, time_delta: f32) {
+    let pos = {
+        let view = world.view::<(&Point, &Mover, &PathFollower)>();
+        let Ok((pos, mover, follower)) = view.try_get(worker) else {
+            return;
+        };
+        if mover.is_moving() || follower.target().is_some() {
+            return;
+        }
+        *pos
+    };
+    let Some((job_id, job_pos)) =
+        reserved_job(world, worker, owner).or_else(|| reserve_job(world, worker, owner, pos))
+    else {
+        return;
+    };
+
+    if job_pos != pos {
+        let is_reachable = find_path(&world.expect_resource::<MapField>(), pos, job_pos).is_some();
+        if !is_reachable {
+            let kind = world.view::<&PlanJob>().try_get(job_id).map(|j| j.kind);
+            if let Ok(kind) = kind {
+                finish_job(
+                    world,
+                    job_id,
+                    kind,
+                    job_pos,
+                    JobOutcome::Failed("unreachable"),
+                );
+            }
+            return;
+        }
+        if let Ok(f) = world.view::<&mut PathFollower>().try_get_mut(worker) {
+            f.go_to(job_pos);
+        }
+        return;
+    }
+
+    let done = {
+        let mut view = world.view::<&mut PlanJob>();
+        let Ok(job) = view.try_get_mut(job_id) else {
+            return;
+        };
+        job.progress += time_delta;
+        (job.progress >= job.kind.work_time()).then_some(job.kind)
+    };
+    if let Some(kind) = done {
+        let outcome = perform(world, worker, kind, pos);
+        finish_job(world, job_id, kind, pos, outcome);
+    }
+}
+
+fn reserved_job(world: &World, worker: EntityId, owner: EntityId) -> Option<(EntityId, Point)> {
+    world
+        .view::<(Entities, &Point, &PlanJob)>()
+        .filter_relates_to::<ChildOf>(owner)
+        .iter()
+        .find(|(_, _, job)| job.reserved_by == Some(worker))
+        .map(|(e, p, _)| (e.id(), *p))
+}
+
+fn reserve_job(
+    world: &World,
+    worker: EntityId,
+    owner: EntityId,
+    pos: Point,
+) -> Option<(EntityId, Point)> {
+    let mut view = world
+        .view::<(Entities, &Point, &mut PlanJob)>()
+        .filter_relates_to::<ChildOf>(owner);
+    let (e, p, job) = view
+        .iter_mut()
+        .filter(|(_, _, job)| job.reserved_by.is_none())
+        .min_by_key(|(_, p, job)| {
+            let distance = (p.x - pos.x).abs() + (p.y - pos.y).abs();
+            (Reverse(job.priority), distance, p.x, p.y)
+        })?;
+    job.reserved_by = Some(worker);
+    Some((e.id(), *p))
+}
+
+fn change_tile(world: &World, pos: Point, tile: MapTile) {
+    set_map_tile(&mut world.expect_resource_mut::<MapField>(), pos, tile);
+    world.expect_resource_mut::<MapRevision>().bump();
+}
+
+fn perform(world: &mut World, worker: EntityId, kind: JobKind, pos: Point) -> JobOutcome {
+    let tile = map_tile(&world.expect_resource::<MapField>(), pos);
+    match kind {
+        JobKind::Till => match tile {
+            Some(MapTile::Grass | MapTile::Ground) => {
+                change_tile(world, pos, MapTile::Ground);
+                world.expect_resource_mut::<Soil>().till(pos);
+                JobOutcome::Completed
+            }
+            _ => JobOutcome::Failed("the soil can not be tilled"),
+        },
+        JobKind::Sow if !world.expect_resource::<Calendar>().season().is_sowing() => {
+            JobOutcome::Failed("it is not the sowing season")
+        }
+        JobKind::Sow => match crop_to_plant(world, pos) {
+            Some(crop) => {
+                create_plant_flow(world, pos, &crop);
+                JobOutcome::Completed
+            }
+            None => JobOutcome::Failed("the crop can not be sown here"),
+        },
+        JobKind::Water => {
+            world.expect_resource_mut::<Soil>().water(pos);
+            JobOutcome::Completed
+        }
+        JobKind::Harvest => {
+            if harvest(world, worker, pos) {
+                JobOutcome::Completed
+            } else {
+                JobOutcome::Failed("nothing is ripe")
+            }
+        }
+        JobKind::Build => match tile {
+            Some(MapTile::Grass | MapTile::Ground) if !has_plant(world, pos) => {
+                change_tile(world, pos, MapTile::Fence);
+                JobOutcome::Completed
+            }
+            _ => JobOutcome::Failed("a fence needs clear firm ground"),
+        },
+        JobKind::Clear => {
+            if tile == Some(MapTile::Forest) {
+                change_tile(world, pos, MapTile::Grass);
+            }
+            let plants: Vec<EntityId> = world
+                .view::<(Entities, &Point, &Plant)>()
+                .iter()
+                .filter(|(_, p, _)| **p == pos)
+                .map(|(e, ..)| e.id())
+                .collect();
+            for plant in plants {
+                let _ = world.despawn(plant);
+            }
+            JobOutcome::Completed
+        }
+    }
+}
+
+fn has_plant(world: &World, pos: Point) -> bool {
+    world
+        .view::<(&Point, &Plant)>()
+        .iter()
+        .any(|(p, _)| *p == pos)
+}
+
+/// Selected crop if it can grow at `pos` and the tile is not occupied by another plant
+pub fn crop_to_plant(world: &World, pos: Point) -> Option<String> {
+    if has_plant(world, pos) {
+        return None;
+    }
+    let crops = world.expect_resource::<Crops>();
+    let crop = crops.by_index(world.expect_resource::<SelectedCrop>().0);
+    let can_grow = map_tile(&world.expect_resource::<MapField>(), pos)
+        .is_some_and(|t| t.properties().fertile && crop.grows_on(t));
+    can_grow.then(|| crop.name.clone())
+}
@@ -0,0 +1,277 @@
+use std::error::Error;
+
+pub mod ambience;
+mod calendar;
+mod camera;
+pub mod components;
+mod control;
+mod crops;
+mod flow_timer;
+mod fov;
+mod game_clock;
+pub mod gui;
+pub mod headless;
+pub mod input;
+mod inventory;
+mod jobs;
+mod lighting;
+mod math;
+mod message_log;
+#[cfg(feature = "mouse")]
+mod mouse;
+pub mod rng;
+mod save;
+pub mod spawn;
+pub mod terminal_constants;
+mod weather;
+
+use ambience::{
+    generator::MapPreset,
+    map::{MapField, MapRevision, nearest_walkable},
+    soil::{Soil, process_soil},
+};
+use calendar::{Calendar, advance_calendar};
+use camera::{Camera, PAN_STEP, move_camera};
+use components::{
+    HareBrain, Mover, PathFollower, PendingWork, Plant, Player, Renderable, process_hare_brain,
+    process_mover, process_path_follower,
+};
+use control::{ControlMode, apply_command, player_command, process_auto_mode};
+use crops::{CROPS_PATH, SelectedCrop, load_crops};
+use edict::{entity::EntityId, flow::Flows, scheduler::Scheduler, world::World};
+use flow_timer::{current_time, init_flow_timers};
+use fov::{RevealedTiles, Viewshed, process_viewshed, reveal_seen_tiles};
+use game_clock::GameClock;
+use gui::minimap::minimap_to_map;
+use input::{Command, Input, InputLog, KeyBindings};
+use inventory::Inventory;
+use jobs::{JobEvents, JobKind, JobOutcome, PlanJob, SelectedJob};
+use lighting::{LightSource, Lighting};
+use message_log::{MessageLog, Severity};
+#[cfg(feature = "mouse")]
+use mouse::MouseInput;
+use rltk::{DrawBatch, GameState, Point, Rect, Rltk, render_draw_buffer};
+use rng::GameRng;
+use spawn::{create_campfire, create_hut, create_player, start_hare};
+use terminal_constants::Consoles;
+use weather::{Weather, process_weather};
+
+pub fn init_world(map: MapField, camera_position: Point) -> World {
+    let mut world = World::new();
+    world.insert_resource(Camera::new(camera_position));
+    world.insert_resource(Soil::new(&map));
+    world.insert_resource(map);
+    world.insert_resource(MapRevision::default());
+    world.insert_resource(DrawBatch::new());
+    world.insert_resource(GameClock::new());
+    world.insert_resource(Calendar::new(0.));
+    world.insert_resource(JobEvents::default());
+    world.insert_resource(MessageLog::default());
+    world.insert_resource(RevealedTiles::new());
+
+    world.ensure_external_registered::<Point>();
+    world.ensure_external_registered::<Rect>();
+    world.ensure_component_registered::<Renderable>();
+    world.ensure_component_registered::<Plant>();
+    world.ensure_component_registered::<PlanJob>();
+    world.ensure_component_registered::<Mover>();
+    world.ensure_component_registered::<PathFollower>();
+    world.ensure_component_registered::<PendingWork>();
+    world.ensure_component_registered::<HareBrain>();
+    world.ensure_component_registered::<Inventory>();
+    world.ensure_component_registered::<Player>();
+    world.ensure_component_registered::<Viewshed>();
+    world.ensure_component_registered::<LightSource>();
+    world
+}
+
+/// Value following `flag` on the command line
+pub fn arg_value(flag: &str) -> Option<String> {
+    std::env::args().skip_while(|a| a != flag).nth(1)
+}
+
+/// New game, replaying `--replay <path>` or recording to `--record <path>` when asked
+pub fn new_game_from_args(bindings: KeyBindings) -> Result<State, Box<dyn Error + Send + Sync>> {
+    if let Some(path) = arg_value("--replay") {
+        let log = InputLog::load(&path)?;
+        let mut gs = State::new(GameRng::new(log.seed), log.map, bindings)?;
+        gs.input = Input::replay(log);
+        return Ok(gs);
+    }
+    let seed = GameRng::seed_from_args()?;
+    let preset = MapPreset::from_args()?;
+    let mut gs = State::new(GameRng::new(seed), preset, bindings)?;
+    if let Some(path) = arg_value("--record") {
+        gs.input = Input::record(seed, preset, &path)?;
+    }
+    Ok(gs)
+}
+
+pub struct State {
+    pub world: World,
+    scheduler: Scheduler,
+    pub flows: Flows,
+    pub mode: ControlMode,
+    pub player_id: EntityId,
+    pub cursor_id: EntityId,
+    pub input: Input,
+    pub bindings: KeyBindings,
+    pub is_help_shown: bool,
+    pub is_minimap_shown: bool,
+    /// Minimap cell moved by the movement keys while the minimap is shown
+    pub minimap_marker: Point,
+    pub is_soil_shown: bool,
+    #[cfg(feature = "mouse")]
+    mouse: MouseInput,
+}
+
+impl State {
+    /// New game on a `preset` map, the map and creatures derived from `rng`
+    pub fn new(
+        rng: GameRng,
+        preset: MapPreset,
+        bindings: KeyBindings,
+    ) -> Result<State, Box<dyn Error + Send + Sync>> {
+        let map = preset.generator(rng.sub_seed("map")).generate();
+        let start_position =
+            nearest_walkable(&map, Point::new(20, 50)).ok_or("No walkable tile")?;
+        let hare_position = nearest_walkable(&map, Point::new(0, 50)).ok_or("No walkable tile")?;
+        let hut_position = nearest_walkable(&map, start_position + Point::new(-3, -2))
+            .ok_or("No walkable tile")?;
+        let campfire_position =
+            nearest_walkable(&map, start_position + Point::new(2, 2)).ok_or("No walkable tile")?;
+        let mut world = init_world(map, start_position);
+        world.insert_resource(Weather::new(rng.stream("weather")));
+        world.insert_resource(rng);
+        world.insert_resource(load_crops(CROPS_PATH)?);
+        world.insert_resource(SelectedCrop(0));
+        world.insert_resource(SelectedJob {
+            kind: JobKind::Sow,
+            priority: 1,
+        });
+        start_hare(&mut world, hare_position);
+        create_hut(&mut world, hut_position);
+        create_campfire(&mut world, campfire_position);
+        let player_id = create_player(&mut world, start_position);
+        let cursor_id = world
+            .spawn_external((Rect::with_exact(
+                start_position.x,
+                start_position.y,
+                start_position.x,
+                start_position.y,
+            ),))
+            .id();
+
+        let mut scheduler = Scheduler::new();
+        init_flow_timers(&mut world, &mut scheduler);
+        scheduler.add_system(process_weather);
+        scheduler.add_system(process_soil);
+        scheduler.add_system(process_path_follower);
+        scheduler.add_system(process_mover);
+        scheduler.add_system(process_viewshed);
+        scheduler.add_system(reveal_seen_tiles);
+        scheduler.add_system(process_hare_brain);
+        Ok(State {
+            world,
+            scheduler,
+            flows: Flows::new(),
+            mode: ControlMode::Player,
+            player_id,
+            cursor_id,
+            input: Input::Live,
+            bindings,
+            is_help_shown: false,
+            is_minimap_shown: false,
+            minimap_marker: Point::zero(),
+            is_soil_shown: false,
+            #[cfg(feature = "mouse")]
+            mouse: MouseInput::new(),
+        })
+    }
+    /// Everything of a frame except drawing, shared by the window and the headless runner
+    fn update(&mut self, frame_time: f32, command: Option<Command>) {
+        self.world
+            .expect_resource_mut::<GameClock>()
+            .advance(frame_time);
+        if let Some(command) = command {
+            player_command(self, command);
+        }
+        self.simulate();
+        let mut log = self.world.expect_resource_mut::<MessageLog>();
+        for event in self.world.expect_resource_mut::<JobEvents>().drain() {
+            let severity = match event.outcome {
+                JobOutcome::Completed => Severity::Info,
+                JobOutcome::Failed(_) => Severity::Warning,
+                JobOutcome::Cancelled => Severity::Info,
+            };
+            log.post(severity, event.to_string());
+        }
+        log.stamp(current_time(&self.world));
+    }
+    /// Command of the bound key pressed in the frame, or of the mouse
+    fn frame_command(&mut self, ctx: &mut Rltk) -> Option<Command> {
+        let is_free_look = self.world.expect_resource::<Camera>().is_free_look();
+        // The mouse is polled every frame to follow its buttons
+        #[cfg(feature = "mouse")]
+        let mouse = {
+            let start_view = self.get_camera_start_view();
+            self.mouse
+                .command(ctx, start_view, self.is_minimap_shown, is_free_look)
+        };
+        #[cfg(not(feature = "mouse"))]
+        let mouse = None;
+        let command = ctx.key.and_then(|k| self.bindings.command(k));
+        // The movement keys move the minimap marker and the camera with it, or else pan
+        // the free looking camera
+        let command = match command {
+            Some(Command::Move(delta_x, delta_y)) if self.is_minimap_shown => {
+                let marker = self.minimap_marker + Point::new(delta_x, delta_y);
+                Some(Command::JumpCamera(minimap_to_map(marker)))
+            }
+            Some(Command::Move(delta_x, delta_y)) if is_free_look => {
+                Some(Command::PanCamera(delta_x * PAN_STEP, delta_y * PAN_STEP))
+            }
+            command => command,
+        };
+        command.or(mouse)
+    }
+    /// Runs the ticks the clock has accumulated, the only place the simulation advances
+    fn simulate(&mut self) {
+        loop {
+            let tick = self.world.expect_resource::<GameClock>().tick_count();
+            while let Some(command) = self.input.due_command(tick) {
+                apply_command(self, command);
+            }
+            if !self.world.expect_resource_mut::<GameClock>().next_tick() {
+                break;
+            }
+            process_auto_mode(self);
+            self.flows.execute(&mut self.world);
+            self.scheduler.run_sequential(&mut self.world);
+            advance_calendar(&mut self.world);
+        }
+    }
+}
+
+impl GameState for State {
+    fn tick(&mut self, context: &mut Rltk) {
+        let command = self.frame_command(context);
+        self.update(context.frame_time_ms, command);
+        move_camera(self);
+        let mut draw_batch = DrawBatch::new();
+        draw_batch.target(Consoles::AdditionalVga.num());
+        gui::info::draw_info(self, &mut draw_batch);
+        gui::log::draw_log(self, &mut draw_batch);
+
+        let mut draw_batch = DrawBatch::new();
+        draw_batch.target(Consoles::Main.num());
+        let lighting = Lighting::new(&self.world);
+        camera::render_map(self, &lighting, &mut draw_batch);
+        camera::render_dynamic(self, &lighting, &mut draw_batch);
+        gui::hud::draw_hud(self, &mut draw_batch);
+        gui::minimap::draw_minimap(self, &mut draw_batch);
+        gui::help::draw_help(self, &mut draw_batch);
+
+        render_draw_buffer(context).expect("Render error");
+    }
+}
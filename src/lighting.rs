@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+
+use edict::{prelude::Component, world::World};
+use rltk::{ColorPair, DistanceAlg, Point, RGBA};
+use serde::{Deserialize, Serialize};
+
+use crate::{calendar::Calendar, weather::Weather};
+
+pub const CAMPFIRE_LIGHT: (u8, u8, u8) = rltk::ORANGE;
+pub const LANTERN_LIGHT: (u8, u8, u8) = rltk::LIGHT_YELLOW;
+pub const WINDOW_LIGHT: (u8, u8, u8) = rltk::KHAKI;
+
+/// Lights the tiles around the entity, fading out towards `radius`
+#[derive(Component, Serialize, Deserialize)]
+pub struct LightSource {
+    radius: i32,
+    color: (u8, u8, u8),
+}
+
+impl LightSource {
+    pub fn new(radius: i32, color: (u8, u8, u8)) -> Self {
+        LightSource { radius, color }
+    }
+}
+
+/// Light falling on the tiles in the frame, the sky plus every light source
+pub struct Lighting {
+    ambient: RGBA,
+    lit: HashMap<Point, RGBA>,
+}
+
+impl Lighting {
+    pub fn new(world: &World) -> Self {
+        let daylight = world.expect_resource::<Calendar>().ambient_light();
+        let clouds = world.expect_resource::<Weather>().kind().light();
+        let ambient = RGBA::from_f32(
+            daylight.r * clouds,
+            daylight.g * clouds,
+            daylight.b * clouds,
+            1.,
+        );
+        let mut lit: HashMap<Point, RGBA> = HashMap::new();
+        for (pos, source) in world.view::<(&Point, &LightSource)>().iter() {
+            let color = RGBA::named(source.color);
+            for x in -source.radius..=source.radius {
+                for y in -source.radius..=source.radius {
+                    let p = *pos + Point::new(x, y);
+                    let distance = DistanceAlg::Pythagoras.distance2d(*pos, p);
+                    let strength = 1. - distance / source.radius as f32;
+                    if strength <= 0. {
+                        continue;
+                    }
+                    let light = lit.entry(p).or_insert(ambient);
+                    light.r += color.r * strength;
+                    light.g += color.g * strength;
+                    light.b += color.b * strength;
+                }
+            }
+        }
+        Lighting { ambient, lit }
+    }
+    pub fn light(&self, p: Point) -> RGBA {
+        let light = self.lit.get(&p).copied().unwrap_or(self.ambient);
+        RGBA::from_f32(light.r.min(1.), light.g.min(1.), light.b.min(1.), 1.)
+    }
+    /// `color` as seen on the tile `p`
+    pub fn tint(&self, p: Point, color: ColorPair) -> ColorPair {
+        let light = self.light(p);
+        let shade = |c: RGBA| RGBA::from_f32(c.r * light.r, c.g * light.g, c.b * light.b, c.a);
+        ColorPair::new(shade(color.fg), shade(color.bg))
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::VecDeque;
+
+use edict::world::World;
+use rltk::RGBA;
+
+/// Older messages are forgotten
+const MAX_MESSAGES: usize = 200;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Severity {
+    Info,
+    Good,
+    Warning,
+    Bad,
+}
+
+impl Severity {
+    pub fn color(&self) -> RGBA {
+        match self {
+            Severity::Info => RGBA::named(rltk::LIGHT_GRAY),
+            Severity::Good => RGBA::named(rltk::LIGHT_GREEN),
+            Severity::Warning => RGBA::named(rltk::YELLOW),
+            Severity::Bad => RGBA::named(rltk::RED),
+        }
+    }
+}
+
+pub struct Message {
+    /// Game milliseconds when the message was posted
+    pub time: f32,
+    pub severity: Severity,
+    pub text: String,
+}
+
+/// Messages for the player. Systems post without knowing the game time,
+/// the messages of a frame are stamped together once the frame is simulated
+#[derive(Default)]
+pub struct MessageLog {
+    messages: VecDeque<Message>,
+    pending: Vec<(Severity, String)>,
+    /// Number of the newest messages scrolled out of the panel
+    scroll: usize,
+}
+
+impl MessageLog {
+    pub fn post(&mut self, severity: Severity, text: impl Into<String>) {
+        self.pending.push((severity, text.into()));
+    }
+    pub fn stamp(&mut self, time: f32) {
+        for (severity, text) in self.pending.drain(..) {
+            if self.messages.len() == MAX_MESSAGES {
+                self.messages.pop_front();
+            }
+            self.messages.push_back(Message {
+                time,
+                severity,
+                text,
+            });
+            // A scrolled panel keeps showing the same messages
+            if self.scroll > 0 {
+                self.scroll += 1;
+            }
+        }
+        self.scroll = self.scroll.min(self.messages.len().saturating_sub(1));
+    }
+    /// Positive `delta` scrolls back to older messages
+    pub fn scroll(&mut self, delta: i8) {
+        let max_scroll = self.messages.len().saturating_sub(1);
+        self.scroll = self
+            .scroll
+            .saturating_add_signed(delta as isize)
+            .min(max_scroll);
+    }
+    /// The `rows` messages of the panel, newest last
+    pub fn visible(&self, rows: usize) -> impl Iterator<Item = &Message> {
+        let end = self.messages.len() - self.scroll.min(self.messages.len());
+        self.messages.range(end.saturating_sub(rows)..end)
+    }
+    pub fn is_scrolled(&self) -> bool {
+        self.scroll > 0
+    }
+}
+
+pub fn post_message(world: &World, severity: Severity, text: impl Into<String>) {
+    world
+        .expect_resource_mut::<MessageLog>()
+        .post(severity, text);
+}
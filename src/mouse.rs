@@ -0,0 +1,137 @@
+use rltk::{INPUT, Point, Rect, Rltk};
+
+use crate::{
+    gui::{
+        layout::{MAIN_VIEW_POSITION, MINIMAP_POSITION},
+        minimap::minimap_to_map,
+    },
+    input::Command,
+    terminal_constants::Consoles,
+};
+
+const LEFT_BUTTON: usize = 0;
+const RIGHT_BUTTON: usize = 1;
+/// Map cells per second panned by edge scrolling
+const EDGE_SCROLL_SPEED: f32 = 20.;
+
+/// Turns the mouse over the main view into commands: a click walks the player to the cell,
+/// a drag sets the cursor rectangle and a right click cancels
+pub struct MouseInput {
+    /// Map cell where the left button went down
+    drag_start: Option<Point>,
+    is_right_pressed: bool,
+    /// The left button went down on the minimap
+    is_minimap_drag: bool,
+    /// Map cell under the pointer
+    hovered: Option<Point>,
+    /// Cells of edge scrolling accumulated below a whole one
+    edge_scroll: f32,
+}
+
+impl MouseInput {
+    pub fn new() -> Self {
+        MouseInput {
+            drag_start: None,
+            is_right_pressed: false,
+            is_minimap_drag: false,
+            hovered: None,
+            edge_scroll: 0.,
+        }
+    }
+    pub fn hovered(&self) -> Option<Point> {
+        self.hovered
+    }
+    /// Command finished by the mouse in this frame, `start_view` translates the view to the map
+    pub fn command(
+        &mut self,
+        ctx: &mut Rltk,
+        start_view: Point,
+        is_minimap_shown: bool,
+        is_free_look: bool,
+    ) -> Option<Command> {
+        ctx.set_active_console(Consoles::Main.num());
+        let cell = ctx.mouse_point();
+        let (is_left_pressed, is_right_pressed) = {
+            let input = INPUT.lock();
+            (
+                input.is_mouse_button_pressed(LEFT_BUTTON),
+                input.is_mouse_button_pressed(RIGHT_BUTTON),
+            )
+        };
+        let is_right_click = is_right_pressed && !self.is_right_pressed;
+        self.is_right_pressed = is_right_pressed;
+
+        // Holding the button pressed on the minimap drags the viewport along
+        let is_on_minimap = MINIMAP_POSITION.point_in_rect(cell) && self.drag_start.is_none();
+        if is_minimap_shown && (self.is_minimap_drag || is_on_minimap) {
+            self.is_minimap_drag = is_left_pressed;
+            self.hovered = None;
+            let is_clicked = is_left_pressed || ctx.left_click;
+            return is_clicked.then(|| Command::JumpCamera(minimap_to_map(cell)));
+        }
+        let is_in_view = MAIN_VIEW_POSITION.point_in_rect(cell);
+        self.hovered = is_in_view.then(|| cell + start_view);
+        if is_left_pressed {
+            if self.drag_start.is_none() && is_in_view {
+                self.drag_start = Some(cell + start_view);
+            }
+            return None;
+        }
+        // Both button events of a quick click may come within one frame
+        if ctx.left_click && self.drag_start.is_none() && is_in_view {
+            return Some(Command::GoTo(cell + start_view));
+        }
+        if let Some(start) = self.drag_start.take() {
+            let end = clamp_to_view(cell) + start_view;
+            if start == end {
+                return Some(Command::GoTo(end));
+            }
+            return Some(Command::Designate(Rect::with_exact(
+                start.x, start.y, end.x, end.y,
+            )));
+        }
+        if is_right_click && is_in_view {
+            return Some(Command::Cancel);
+        }
+        if is_free_look {
+            return self.edge_scroll(cell, ctx.frame_time_ms);
+        }
+        None
+    }
+    /// Pans the free looking camera while the pointer rests on the edge of the main view
+    fn edge_scroll(&mut self, cell: Point, frame_time: f32) -> Option<Command> {
+        let delta_x = edge_direction(cell.x, MAIN_VIEW_POSITION.x1, MAIN_VIEW_POSITION.x2);
+        let delta_y = edge_direction(cell.y, MAIN_VIEW_POSITION.y1, MAIN_VIEW_POSITION.y2);
+        if delta_x == 0 && delta_y == 0 {
+            self.edge_scroll = 0.;
+            return None;
+        }
+        self.edge_scroll += frame_time * EDGE_SCROLL_SPEED / 1000.;
+        let cells = self.edge_scroll as i32;
+        if cells == 0 {
+            return None;
+        }
+        self.edge_scroll -= cells as f32;
+        Some(Command::PanCamera(delta_x * cells, delta_y * cells))
+    }
+}
+
+fn clamp_to_view(cell: Point) -> Point {
+    Point::new(
+        cell.x
+            .clamp(MAIN_VIEW_POSITION.x1, MAIN_VIEW_POSITION.x2 - 1),
+        cell.y
+            .clamp(MAIN_VIEW_POSITION.y1, MAIN_VIEW_POSITION.y2 - 1),
+    )
+}
+
+/// -1 or 1 when `coord` is within the edge cell of `low..high` or beyond it
+fn edge_direction(coord: i32, low: i32, high: i32) -> i32 {
+    if coord <= low {
+        -1
+    } else if coord >= high - 1 {
+        1
+    } else {
+        0
+    }
+}
@@ -0,0 +1,60 @@
+use std::error::Error;
+
+use rltk::RandomNumberGenerator;
+use serde::{Deserialize, Serialize};
+
+use crate::arg_value;
+
+/// Source of all randomness of a session. Subsystems never seed from entropy, they derive
+/// their own generators from the session seed so a run is reproduced by the seed alone
+#[derive(Serialize, Deserialize)]
+pub struct GameRng {
+    seed: u64,
+    /// Generators handed out by `next_stream`
+    streams: u64,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        GameRng { seed, streams: 0 }
+    }
+    /// Seed from the `--seed <u64>` command-line flag, random when it is missing
+    pub fn seed_from_args() -> Result<u64, Box<dyn Error + Send + Sync>> {
+        match arg_value("--seed") {
+            Some(seed) => seed
+                .parse()
+                .map_err(|_| format!("--seed expects an unsigned integer, got {seed}").into()),
+            None => Ok(RandomNumberGenerator::new().rand::<u64>()),
+        }
+    }
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+    /// Seed of the subsystem used once per session, like map generation or weather
+    pub fn sub_seed(&self, name: &str) -> u64 {
+        mix(self.seed ^ fnv1a(name))
+    }
+    pub fn stream(&self, name: &str) -> RandomNumberGenerator {
+        RandomNumberGenerator::seeded(self.sub_seed(name))
+    }
+    /// Generator of a new instance of the subsystem, like every creature brain
+    pub fn next_stream(&mut self, name: &str) -> RandomNumberGenerator {
+        self.streams += 1;
+        RandomNumberGenerator::seeded(mix(self.sub_seed(name) ^ self.streams))
+    }
+}
+
+// std hashers are not guaranteed to be stable between releases, seeds have to be
+fn fnv1a(name: &str) -> u64 {
+    name.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+// splitmix64 finalizer
+fn mix(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,579 @@
+use std::{collections::HashMap, fmt, fs, io};
+
+use edict::{
+    entity::EntityId,
+    flow::Flows,
+    prelude::{ChildOf, Component},
+    query::Entities,
+    world::World,
+};
+use rltk::{Point, Rect};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    State,
+    ambience::{
+        map::{MAP_DIMENSION, MapField, MapTile, empty_map},
+        soil::{Soil, SoilTile},
+    },
+    calendar::Calendar,
+    camera::Camera,
+    components::{HareBrain, Mover, PathFollower, Plant, Player, Renderable, rotate_render_stack},
+    control::ControlMode,
+    crops::{Crops, SelectedCrop},
+    flow_timer::{current_time, insert_flow_timers},
+    fov::{RevealedTiles, Viewshed},
+    init_world,
+    inventory::Inventory,
+    jobs::{PlanJob, SelectedJob},
+    lighting::{LANTERN_LIGHT, LightSource},
+    message_log::MessageLog,
+    rng::GameRng,
+    spawn::{HARE_SIGHT, LANTERN_RADIUS, PLAYER_SIGHT},
+    weather::Weather,
+};
+
+pub const SAVE_PATH: &str = "savegame.json";
+const SAVE_VERSION: u64 = 9;
+
+/// `MIGRATIONS[n]` upgrades a save of version `n + 1` to version `n + 2`
+const MIGRATIONS: &[fn(&mut Value)] = &[
+    migrate_plant_crops,
+    migrate_typed_jobs,
+    migrate_rng,
+    migrate_sight,
+    migrate_lights,
+    migrate_weather,
+    migrate_soil,
+    migrate_soil_values,
+];
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    Format(serde_json::Error),
+    Version(u64),
+    Corrupted(&'static str),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Io(e) => write!(f, "save file error: {e}"),
+            SaveError::Format(e) => write!(f, "save format error: {e}"),
+            SaveError::Version(v) => write!(f, "unsupported save version {v}"),
+            SaveError::Corrupted(what) => write!(f, "corrupted save: {what}"),
+        }
+    }
+}
+
+impl From<io::Error> for SaveError {
+    fn from(e: io::Error) -> Self {
+        SaveError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(e: serde_json::Error) -> Self {
+        SaveError::Format(e)
+    }
+}
+
+#[derive(Serialize)]
+struct SaveGame<'a> {
+    version: u64,
+    rng: &'a GameRng,
+    weather: &'a Weather,
+    map: Vec<MapTile>,
+    /// Tiles seen by the player, see `RevealedTiles::to_save`
+    revealed: String,
+    /// Soil of every tile in the order of `map`
+    soil: &'a [SoilTile],
+    camera: Point,
+    current_time: f32,
+    cursor: Rect,
+    player: usize,
+    entities: Vec<SavedEntity<'a>>,
+}
+
+#[derive(Serialize)]
+struct SavedEntity<'a> {
+    point: &'a Point,
+    renderable: Option<&'a Renderable>,
+    mover: Option<&'a Mover>,
+    path_follower: Option<&'a PathFollower>,
+    hare_brain: Option<&'a HareBrain>,
+    plan_job: Option<&'a PlanJob>,
+    plant: Option<&'a Plant>,
+    inventory: Option<&'a Inventory>,
+    viewshed: Option<&'a Viewshed>,
+    light: Option<&'a LightSource>,
+    /// Index of the owner in `entities`
+    child_of: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct LoadedGame {
+    rng: GameRng,
+    weather: Weather,
+    map: Vec<MapTile>,
+    revealed: String,
+    soil: Vec<SoilTile>,
+    camera: Point,
+    current_time: f32,
+    cursor: Rect,
+    player: usize,
+    entities: Vec<LoadedEntity>,
+}
+
+#[derive(Deserialize)]
+struct LoadedEntity {
+    point: Point,
+    renderable: Option<Renderable>,
+    mover: Option<Mover>,
+    path_follower: Option<PathFollower>,
+    hare_brain: Option<HareBrain>,
+    plan_job: Option<PlanJob>,
+    plant: Option<Plant>,
+    inventory: Option<Inventory>,
+    viewshed: Option<Viewshed>,
+    light: Option<LightSource>,
+    child_of: Option<usize>,
+}
+
+pub fn save_game(gs: &State, path: &str) -> Result<(), SaveError> {
+    let world = &gs.world;
+
+    // Only workers own planned jobs for now
+    let mut owners: HashMap<EntityId, EntityId> = HashMap::new();
+    for (owner, _) in world.view::<(Entities, &Mover)>().iter() {
+        let jobs = world
+            .view::<(Entities, &PlanJob)>()
+            .filter_relates_to::<ChildOf>(owner.id());
+        for (job, _) in jobs.iter() {
+            owners.insert(job.id(), owner.id());
+        }
+    }
+
+    let view = world.view::<(
+        Entities,
+        &Point,
+        Option<&Renderable>,
+        Option<&Mover>,
+        Option<&PathFollower>,
+        Option<&HareBrain>,
+        Option<&PlanJob>,
+        Option<&Plant>,
+        Option<&Inventory>,
+    )>();
+    let items: Vec<_> = view.iter().collect();
+    let indices: HashMap<EntityId, usize> = items
+        .iter()
+        .enumerate()
+        .map(|(i, (e, ..))| (e.id(), i))
+        .collect();
+    let viewsheds = world.view::<&Viewshed>();
+    let lights = world.view::<&LightSource>();
+
+    let entities = items
+        .iter()
+        .map(
+            |(
+                e,
+                point,
+                renderable,
+                mover,
+                path_follower,
+                hare_brain,
+                plan_job,
+                plant,
+                inventory,
+            )| {
+                SavedEntity {
+                    point,
+                    renderable: *renderable,
+                    mover: *mover,
+                    path_follower: *path_follower,
+                    hare_brain: *hare_brain,
+                    plan_job: *plan_job,
+                    plant: *plant,
+                    inventory: *inventory,
+                    viewshed: viewsheds.try_get(e.id()).ok(),
+                    light: lights.try_get(e.id()).ok(),
+                    child_of: owners
+                        .get(&e.id())
+                        .and_then(|owner| indices.get(owner).copied()),
+                }
+            },
+        )
+        .collect();
+
+    let rng = world.expect_resource::<GameRng>();
+    let weather = world.expect_resource::<Weather>();
+    let soil = world.expect_resource::<Soil>();
+    let save = SaveGame {
+        version: SAVE_VERSION,
+        rng: &rng,
+        weather: &weather,
+        map: world
+            .expect_resource::<MapField>()
+            .iter()
+            .flatten()
+            .copied()
+            .collect(),
+        revealed: world.expect_resource::<RevealedTiles>().to_save(),
+        soil: soil.tiles(),
+        camera: *world.expect_resource::<Camera>().get_position(),
+        current_time: current_time(world),
+        cursor: *world
+            .view::<&Rect>()
+            .try_get(gs.cursor_id)
+            .expect("Cursor does not exist"),
+        player: *indices.get(&gs.player_id).expect("Player does not exist"),
+        entities,
+    };
+    fs::write(path, serde_json::to_string(&save)?)?;
+    Ok(())
+}
+
+pub fn load_game(gs: &mut State, path: &str) -> Result<(), SaveError> {
+    let mut value: Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+    migrate(&mut value)?;
+    let save: LoadedGame = serde_json::from_value(value)?;
+
+    let count = save.entities.len();
+    if save.player >= count {
+        return Err(SaveError::Corrupted("player index"));
+    }
+    let relations: Vec<(usize, usize)> = save
+        .entities
+        .iter()
+        .enumerate()
+        .filter_map(|(i, e)| e.child_of.map(|owner| (i, owner)))
+        .collect();
+    if relations.iter().any(|&(_, owner)| owner >= count) {
+        return Err(SaveError::Corrupted("owner index"));
+    }
+    // Plants of crops removed from the data file would never grow
+    let is_crop_unknown = {
+        let crops = gs.world.expect_resource::<Crops>();
+        save.entities
+            .iter()
+            .filter_map(|e| e.plant.as_ref())
+            .any(|plant| crops.get(plant.crop()).is_none())
+    };
+    if is_crop_unknown {
+        return Err(SaveError::Corrupted("unknown crop"));
+    }
+
+    let mut world = init_world(restore_map(&save.map)?, save.camera);
+    insert_flow_timers(&mut world, save.current_time);
+    world.insert_resource(Calendar::new(save.current_time));
+    world.insert_resource(save.weather);
+    world.insert_resource(save.rng);
+    world.insert_resource(RevealedTiles::from_save(&save.revealed));
+    let soil = Soil::from_save(save.soil, &world.expect_resource::<MapField>())
+        .ok_or(SaveError::Corrupted("soil"))?;
+    world.insert_resource(soil);
+
+    let mut ids = Vec::with_capacity(count);
+    for e in save.entities {
+        let id = world.spawn_external((e.point,)).id();
+        let is_plant = e.plant.is_some();
+        insert_optional(&mut world, id, e.renderable);
+        insert_optional(&mut world, id, e.mover);
+        insert_optional(&mut world, id, e.path_follower);
+        insert_optional(&mut world, id, e.hare_brain);
+        insert_optional(&mut world, id, e.plan_job);
+        insert_optional(&mut world, id, e.plant);
+        insert_optional(&mut world, id, e.inventory);
+        insert_optional(&mut world, id, e.viewshed);
+        insert_optional(&mut world, id, e.light);
+        // Growth continues from the stored stage
+        if is_plant {
+            world.spawn_flow_for(id, rotate_render_stack);
+        }
+        ids.push(id);
+    }
+    for (child, owner) in relations {
+        let _ = world.insert_relation(ids[child], ChildOf, ids[owner]);
+    }
+    let cursor_id = world.spawn_external((save.cursor,)).id();
+    world
+        .insert(ids[save.player], Player)
+        .expect("Entity was just spawned");
+
+    // Definitions, player choices and messages are not part of the save and are carried over
+    let crops = gs
+        .world
+        .remove_resource::<Crops>()
+        .expect("Crops are not loaded");
+    let selected_crop = gs
+        .world
+        .remove_resource::<SelectedCrop>()
+        .expect("Crops are not loaded");
+    let selected_job = gs
+        .world
+        .remove_resource::<SelectedJob>()
+        .expect("Selected job does not exist");
+    let messages = gs
+        .world
+        .remove_resource::<MessageLog>()
+        .expect("Message log does not exist");
+    world.insert_resource(crops);
+    world.insert_resource(selected_crop);
+    world.insert_resource(selected_job);
+    world.insert_resource(messages);
+
+    gs.world = world;
+    gs.flows = Flows::new();
+    gs.mode = ControlMode::Player;
+    gs.player_id = ids[save.player];
+    gs.cursor_id = cursor_id;
+    Ok(())
+}
+
+fn migrate(value: &mut Value) -> Result<(), SaveError> {
+    let version = value["version"].as_u64().unwrap_or(0);
+    if version == 0 || version > SAVE_VERSION {
+        return Err(SaveError::Version(version));
+    }
+    for migration in &MIGRATIONS[(version - 1) as usize..] {
+        migration(value);
+    }
+    value["version"] = SAVE_VERSION.into();
+    Ok(())
+}
+
+/// Version 1 knew only the rye stages of 1000 ms and kept the end of the current stage
+fn migrate_plant_crops(value: &mut Value) {
+    let Some(entities) = value["entities"].as_array_mut() else {
+        return;
+    };
+    for plant in entities
+        .iter_mut()
+        .filter_map(|e| e["plant"].as_object_mut())
+    {
+        let stage_start = plant
+            .remove("stage_end")
+            .and_then(|end| end.as_f64())
+            .map(|end| end - 1000.);
+        plant.insert("crop".to_string(), "rye".into());
+        plant.insert("stage_start".to_string(), stage_start.into());
+    }
+}
+
+/// Version 2 jobs were untyped plans to sow
+fn migrate_typed_jobs(value: &mut Value) {
+    let Some(entities) = value["entities"].as_array_mut() else {
+        return;
+    };
+    for job in entities
+        .iter_mut()
+        .filter_map(|e| e["plan_job"].as_object_mut())
+    {
+        job.insert("kind".to_string(), "Sow".into());
+        job.insert("priority".to_string(), 1.into());
+        job.insert("progress".to_string(), 0.into());
+    }
+}
+
+/// Version 3 sessions were seeded from entropy, the seed is unknown
+fn migrate_rng(value: &mut Value) {
+    value["rng"] = serde_json::json!({ "seed": 0, "streams": 0 });
+}
+
+/// Version 4 had no sight, the player and the hares get the ranges of new ones
+/// and start on an unexplored map
+fn migrate_sight(value: &mut Value) {
+    value["revealed"] = "".into();
+    let player = value["player"].as_u64();
+    let Some(entities) = value["entities"].as_array_mut() else {
+        return;
+    };
+    for (i, e) in entities.iter_mut().enumerate() {
+        let range = if Some(i as u64) == player {
+            PLAYER_SIGHT
+        } else if !e["hare_brain"].is_null() {
+            HARE_SIGHT
+        } else {
+            continue;
+        };
+        e["viewshed"] = serde_json::json!({ "range": range });
+    }
+}
+
+/// Version 5 had no lights, the player gets the lantern of a new one
+fn migrate_lights(value: &mut Value) {
+    let Some(player) = value["player"].as_u64() else {
+        return;
+    };
+    if let Some(e) = value["entities"].get_mut(player as usize) {
+        e["light"] = serde_json::json!({ "radius": LANTERN_RADIUS, "color": LANTERN_LIGHT });
+    }
+}
+
+/// Version 6 had no weather, the save gets a new one from the session seed
+fn migrate_weather(value: &mut Value) {
+    let Ok(rng) = serde_json::from_value::<GameRng>(value["rng"].clone()) else {
+        return;
+    };
+    if let Ok(weather) = serde_json::to_value(Weather::new(rng.stream("weather"))) {
+        value["weather"] = weather;
+    }
+}
+
+/// Version 7 had no soil, the tiles get the soil of a new map
+fn migrate_soil(value: &mut Value) {
+    value["soil"] = "".into();
+}
+
+/// Version 8 kept three digits per soil tile, the moisture and the fertility in ninths
+/// and the tillage. Saves with other characters are left to fail the loading
+fn migrate_soil_values(value: &mut Value) {
+    let Some(digits) = value["soil"].as_str() else {
+        return;
+    };
+    let Some(digits) = digits
+        .chars()
+        .map(|c| c.to_digit(10))
+        .collect::<Option<Vec<u32>>>()
+        .filter(|d| d.len() % 3 == 0)
+    else {
+        return;
+    };
+    let tiles: Vec<Value> = digits
+        .chunks(3)
+        .map(|d| {
+            serde_json::json!({
+                "moisture": d[0] as f32 / 9.,
+                "fertility": d[1] as f32 / 9.,
+                "is_tilled": d[2] > 0,
+            })
+        })
+        .collect();
+    value["soil"] = tiles.into();
+}
+
+fn restore_map(tiles: &[MapTile]) -> Result<MapField, SaveError> {
+    if tiles.len() != MAP_DIMENSION.0 * MAP_DIMENSION.1 {
+        return Err(SaveError::Corrupted("map size"));
+    }
+    let mut map = empty_map();
+    for (column, saved) in map.iter_mut().zip(tiles.chunks(MAP_DIMENSION.1)) {
+        column.copy_from_slice(saved);
+    }
+    Ok(map)
+}
+
+fn insert_optional<T: Component>(world: &mut World, id: EntityId, component: Option<T>) {
+    if let Some(component) = component {
+        world
+            .insert(id, component)
+            .expect("Entity was just spawned");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+
+    use super::{
+        migrate_lights, migrate_plant_crops, migrate_rng, migrate_sight, migrate_soil,
+        migrate_soil_values, migrate_typed_jobs, migrate_weather,
+    };
+    use crate::{
+        spawn::{HARE_SIGHT, LANTERN_RADIUS, PLAYER_SIGHT},
+        weather::Weather,
+    };
+
+    #[test]
+    fn plants_become_rye_started_a_stage_before_the_end() {
+        let mut save = json!({ "entities": [{ "plant": { "stage": 1, "stage_end": 5000.0 } }] });
+        migrate_plant_crops(&mut save);
+        assert_eq!(
+            save["entities"][0]["plant"],
+            json!({ "stage": 1, "crop": "rye", "stage_start": 4000.0 })
+        );
+    }
+
+    #[test]
+    fn jobs_become_sowing() {
+        let mut save = json!({ "entities": [{ "plan_job": {} }, { "plant": {} }] });
+        migrate_typed_jobs(&mut save);
+        assert_eq!(
+            save["entities"][0]["plan_job"],
+            json!({ "kind": "Sow", "priority": 1, "progress": 0 })
+        );
+        assert_eq!(save["entities"][1], json!({ "plant": {} }));
+    }
+
+    #[test]
+    fn rng_gets_zero_seed() {
+        let mut save = json!({ "entities": [] });
+        migrate_rng(&mut save);
+        assert_eq!(save["rng"], json!({ "seed": 0, "streams": 0 }));
+    }
+
+    #[test]
+    fn player_and_hares_get_sight() {
+        let mut save = json!({
+            "player": 1,
+            "entities": [{ "hare_brain": {} }, { "mover": {} }, { "plant": {} }],
+        });
+        migrate_sight(&mut save);
+        assert_eq!(save["revealed"], "");
+        let range = |i: usize| save["entities"][i]["viewshed"]["range"].clone();
+        assert_eq!(range(0), json!(HARE_SIGHT));
+        assert_eq!(range(1), json!(PLAYER_SIGHT));
+        assert_eq!(range(2), Value::Null);
+    }
+
+    #[test]
+    fn player_gets_lantern() {
+        let mut save = json!({ "player": 1, "entities": [{}, {}] });
+        migrate_lights(&mut save);
+        assert_eq!(save["entities"][0], json!({}));
+        assert_eq!(
+            save["entities"][1]["light"]["radius"],
+            json!(LANTERN_RADIUS)
+        );
+    }
+
+    #[test]
+    fn weather_comes_from_session_seed() {
+        let mut save = json!({ "rng": { "seed": 7, "streams": 0 } });
+        migrate_weather(&mut save);
+        let weather = save["weather"].clone();
+        assert!(serde_json::from_value::<Weather>(weather.clone()).is_ok());
+
+        let mut again = json!({ "rng": { "seed": 7, "streams": 0 } });
+        migrate_weather(&mut again);
+        assert_eq!(again["weather"], weather);
+    }
+
+    #[test]
+    fn soil_starts_over() {
+        let mut save = json!({ "entities": [] });
+        migrate_soil(&mut save);
+        assert_eq!(save["soil"], "");
+    }
+
+    #[test]
+    fn soil_digits_become_values() {
+        let mut save = json!({ "soil": "901000" });
+        migrate_soil_values(&mut save);
+        assert_eq!(
+            save["soil"],
+            json!([
+                { "moisture": 1.0, "fertility": 0.0, "is_tilled": true },
+                { "moisture": 0.0, "fertility": 0.0, "is_tilled": false },
+            ])
+        );
+
+        let mut empty = json!({ "soil": "" });
+        migrate_soil_values(&mut empty);
+        assert_eq!(empty["soil"], json!([]));
+    }
+}
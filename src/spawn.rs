@@ -1,39 +1,86 @@
-use edict::{entity::EntityId, flow::FlowWorld, prelude::ChildOf, world::World};
+use edict::{entity::EntityId, prelude::ChildOf, world::World};
 use rltk::Point;
 
-use crate::components::{HareBrain, Mover, PlanJob, Renderable, rotate_render_stack};
+use crate::{
+    components::{HareBrain, Mover, PathFollower, Plant, Player, Renderable, rotate_render_stack},
+    fov::Viewshed,
+    inventory::Inventory,
+    jobs::{JobKind, PlanJob},
+    lighting::{CAMPFIRE_LIGHT, LANTERN_LIGHT, LightSource, WINDOW_LIGHT},
+    message_log::{Severity, post_message},
+    rng::GameRng,
+};
 
-pub fn create_plan_job(world: &mut World, pos: Point, plan_owner: EntityId) {
+pub const PLAYER_SIGHT: i32 = 12;
+pub const HARE_SIGHT: i32 = 6;
+pub const LANTERN_RADIUS: i32 = 4;
+/// Tiles per game millisecond, a quarter of a second for a step on the ground
+const PLAYER_SPEED: f32 = 0.004;
+
+pub fn create_plan_job(
+    world: &mut World,
+    pos: Point,
+    plan_owner: EntityId,
+    kind: JobKind,
+    priority: u8,
+) {
     let plan_job = world
         .spawn_external((
             pos,
-            Renderable::new_bg('T', rltk::BLUE3, rltk::CADET_BLUE),
-            PlanJob {},
+            Renderable::new_bg(kind.glyph(), rltk::BLUE3, rltk::CADET_BLUE),
+            PlanJob::new(kind, priority),
         ))
         .id();
     let _ = world.insert_relation(plan_job, ChildOf, plan_owner);
 }
 
-pub fn create_plant_flow(world: &mut FlowWorld, pos: Point) {
-    world
-        .spawn_external((pos, Renderable::new_blank()))
-        .spawn_flow(rotate_render_stack);
+pub fn create_plant_flow(world: &mut World, pos: Point, crop: &str) {
+    let plant = world
+        .spawn_external((pos, Renderable::new_blank(), Plant::new(crop)))
+        .id();
+    world.spawn_flow_for(plant, rotate_render_stack);
+}
+
+pub fn create_campfire(world: &mut World, pos: Point) {
+    world.spawn_external((
+        pos,
+        Renderable::new('▲', rltk::ORANGE_RED),
+        LightSource::new(7, CAMPFIRE_LIGHT),
+    ));
+}
+
+/// The farmer's hut, its window lights up the yard at night
+pub fn create_hut(world: &mut World, pos: Point) {
+    world.spawn_external((
+        pos,
+        Renderable::new('⌂', rltk::BURLYWOOD),
+        LightSource::new(3, WINDOW_LIGHT),
+    ));
 }
 
 pub fn start_hare(world: &mut World, pos: Point) {
+    let rand = world.expect_resource_mut::<GameRng>().next_stream("hare");
     world.spawn_external((
         Renderable::new('h', rltk::RED3),
         pos,
         Mover::new_speed(0.005),
-        HareBrain::new(),
+        PathFollower::new(),
+        HareBrain::new(rand),
+        Viewshed::new(HARE_SIGHT),
     ));
+    post_message(world, Severity::Info, "A hare runs into the field");
 }
 pub fn create_player(world: &mut World, pos: Point) -> EntityId {
     world
         .spawn_external((
             pos,
             Renderable::new('Ӂ', rltk::RED),
-            Mover::new_speed(0.001),
+            Mover::new_speed(PLAYER_SPEED),
+            PathFollower::new(),
+            Inventory::new(),
+            Player,
+            Viewshed::new(PLAYER_SIGHT),
+            LightSource::new(LANTERN_RADIUS, LANTERN_LIGHT),
         ))
         .id()
 }
@@ -4,6 +4,7 @@ use crate::gui::layout::{ADDITIONAL_CONSOLE_DIMENSION, MAIN_CONSOLE_DIMENSION};
 
 pub const MAP_Z_ORDER: usize = 0;
 pub const CHAR_Z_ORDER: usize = 5000;
+pub const HELP_Z_ORDER: usize = 10000;
 
 pub const INFO_Z_ORDER: usize = 0;
 
@@ -0,0 +1,286 @@
+use core::fmt;
+use std::collections::{HashMap, HashSet};
+
+use edict::{
+    prelude::{Res, ResMut},
+    view::View,
+};
+use rltk::{Point, RGBA, RandomNumberGenerator};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ambience::map::{MAP_BORDER, MapField, MapRevision, MapTile, map_tile, set_map_tile},
+    calendar::{Calendar, Season},
+    message_log::{MessageLog, Severity},
+};
+
+/// Days of drought after which the plants finishing a stage wither
+const WITHER_DAYS: u32 = 2;
+/// Chance to keep the weather of the previous day when the season allows it
+const PERSISTENCE: f32 = 0.5;
+/// Farthest a flood spreads from the water of the generated map, in tiles
+const FLOOD_REACH: u32 = 3;
+const SIDES: [Point; 4] = [
+    Point::constant(-1, 0),
+    Point::constant(1, 0),
+    Point::constant(0, -1),
+    Point::constant(0, 1),
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum WeatherKind {
+    Clear,
+    Rain,
+    Storm,
+    Drought,
+    Snow,
+}
+
+impl fmt::Display for WeatherKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+impl WeatherKind {
+    /// Chances of the weather in the season, relative to each other
+    fn weights(season: Season) -> [(WeatherKind, u32); 4] {
+        match season {
+            Season::Spring => [
+                (WeatherKind::Clear, 5),
+                (WeatherKind::Rain, 4),
+                (WeatherKind::Storm, 1),
+                (WeatherKind::Drought, 0),
+            ],
+            Season::Summer => [
+                (WeatherKind::Clear, 5),
+                (WeatherKind::Rain, 2),
+                (WeatherKind::Storm, 2),
+                (WeatherKind::Drought, 2),
+            ],
+            Season::Autumn => [
+                (WeatherKind::Clear, 4),
+                (WeatherKind::Rain, 5),
+                (WeatherKind::Storm, 1),
+                (WeatherKind::Drought, 0),
+            ],
+            Season::Winter => [
+                (WeatherKind::Clear, 5),
+                (WeatherKind::Snow, 5),
+                (WeatherKind::Storm, 0),
+                (WeatherKind::Drought, 0),
+            ],
+        }
+    }
+    /// Multiplier of the crop growth speed
+    pub fn growth_rate(&self) -> f32 {
+        match self {
+            WeatherKind::Clear | WeatherKind::Snow => 1.,
+            WeatherKind::Rain => 1.5,
+            WeatherKind::Storm => 1.25,
+            WeatherKind::Drought => 0.5,
+        }
+    }
+    /// Share of the daylight getting through the clouds
+    pub fn light(&self) -> f32 {
+        match self {
+            WeatherKind::Clear | WeatherKind::Drought => 1.,
+            WeatherKind::Rain => 0.8,
+            WeatherKind::Storm => 0.6,
+            WeatherKind::Snow => 0.9,
+        }
+    }
+    /// Soil moisture gained or lost by an hour of the weather
+    pub fn moisture(&self) -> f32 {
+        match self {
+            WeatherKind::Clear => -0.01,
+            WeatherKind::Rain => 0.04,
+            WeatherKind::Storm => 0.06,
+            WeatherKind::Drought => -0.03,
+            WeatherKind::Snow => 0.,
+        }
+    }
+    /// Chance of a shore tile to be flooded, or to dry up when negative
+    fn shore_change(&self) -> f32 {
+        match self {
+            WeatherKind::Clear | WeatherKind::Snow => 0.,
+            WeatherKind::Rain => 0.03,
+            WeatherKind::Storm => 0.15,
+            WeatherKind::Drought => -0.15,
+        }
+    }
+    fn severity(&self) -> Severity {
+        match self {
+            WeatherKind::Clear | WeatherKind::Snow => Severity::Info,
+            WeatherKind::Rain => Severity::Good,
+            WeatherKind::Storm | WeatherKind::Drought => Severity::Warning,
+        }
+    }
+}
+
+/// Land under the flood water, dried back to its own tile
+#[derive(Serialize, Deserialize)]
+struct Flooded {
+    pos: Point,
+    tile: MapTile,
+    /// Tiles from the water of the generated map
+    reach: u32,
+}
+
+/// Weather of the day, rolled at the start of every day from the season
+#[derive(Serialize, Deserialize)]
+pub struct Weather {
+    kind: WeatherKind,
+    /// Calendar day the weather was rolled for
+    day: u32,
+    /// Days in a row with the same weather
+    streak: u32,
+    rand: RandomNumberGenerator,
+    /// Only the flooded tiles dry up, the water of the generated map stays
+    #[serde(default)]
+    flooded: Vec<Flooded>,
+}
+
+impl Weather {
+    pub fn new(rand: RandomNumberGenerator) -> Self {
+        Weather {
+            kind: WeatherKind::Clear,
+            day: 0,
+            streak: 1,
+            rand,
+            flooded: Vec::new(),
+        }
+    }
+    pub fn kind(&self) -> WeatherKind {
+        self.kind
+    }
+    /// The drought has lasted long enough to kill plants
+    pub fn is_withering(&self) -> bool {
+        self.kind == WeatherKind::Drought && self.streak >= WITHER_DAYS
+    }
+    fn roll(&mut self, season: Season) -> WeatherKind {
+        let weights = WeatherKind::weights(season);
+        let is_possible = weights.iter().any(|(kind, w)| *kind == self.kind && *w > 0);
+        if is_possible && self.rand.rand::<f32>() < PERSISTENCE {
+            return self.kind;
+        }
+        let total: u32 = weights.iter().map(|(_, w)| w).sum();
+        let mut roll = self.rand.range(0, total);
+        for (kind, w) in weights {
+            if roll < w {
+                return kind;
+            }
+            roll -= w;
+        }
+        WeatherKind::Clear
+    }
+    /// Floods the land next to the water with `chance`, no farther than `FLOOD_REACH` from
+    /// the water of the generated map. Returns whether the map has changed
+    fn flood(&mut self, map: &mut MapField, occupied: &HashSet<Point>, chance: f32) -> bool {
+        let reaches: HashMap<Point, u32> = self.flooded.iter().map(|f| (f.pos, f.reach)).collect();
+        let water_reach = |p: Point| {
+            (map_tile(map, p) == Some(MapTile::Water))
+                .then(|| reaches.get(&p).copied().unwrap_or(0))
+        };
+        let shore: Vec<Flooded> = MAP_BORDER
+            .point_set()
+            .into_iter()
+            .filter(|p| !occupied.contains(p))
+            .filter_map(|pos| {
+                let tile = map_tile(map, pos)
+                    .filter(|t| matches!(t, MapTile::Ground | MapTile::Grass | MapTile::Forest))?;
+                let reach = SIDES.iter().filter_map(|d| water_reach(pos + *d)).min()? + 1;
+                (reach <= FLOOD_REACH).then_some(Flooded { pos, tile, reach })
+            })
+            .collect();
+        let mut is_changed = false;
+        for flooded in shore {
+            if self.rand.rand::<f32>() < chance {
+                set_map_tile(map, flooded.pos, MapTile::Water);
+                self.flooded.push(flooded);
+                is_changed = true;
+            }
+        }
+        is_changed
+    }
+    /// Dries the flooded tiles next to the land with `chance`, back to the tiles they were.
+    /// Returns whether the map has changed
+    fn recede(&mut self, map: &mut MapField, chance: f32) -> bool {
+        let mut is_changed = false;
+        let rand = &mut self.rand;
+        self.flooded.retain(|f| {
+            // Frozen floods wait for the thaw
+            let is_shore = map_tile(map, f.pos) == Some(MapTile::Water)
+                && SIDES
+                    .iter()
+                    .any(|d| map_tile(map, f.pos + *d).is_some_and(|t| t != MapTile::Water));
+            if !is_shore || rand.rand::<f32>() >= chance {
+                return true;
+            }
+            set_map_tile(map, f.pos, f.tile);
+            is_changed = true;
+            false
+        });
+        is_changed
+    }
+    /// Rain drop or snowflake over the tile `pos` at the game `time`, falling down the screen
+    pub fn precipitation(&self, pos: Point, time: f32) -> Option<(char, RGBA)> {
+        let (glyph, color, density, fall_time, drift) = match self.kind {
+            WeatherKind::Rain => ('\'', rltk::LIGHT_BLUE, 14, 120., 0),
+            WeatherKind::Storm => ('/', rltk::LIGHT_STEEL_BLUE, 5, 60., 1),
+            WeatherKind::Snow => ('*', rltk::WHITE, 18, 400., 0),
+            WeatherKind::Clear | WeatherKind::Drought => return None,
+        };
+        let frame = (time / fall_time) as i32;
+        let hash = cell_hash(pos.x + frame * drift, pos.y - frame);
+        (hash % density == 0).then(|| (glyph, RGBA::named(color)))
+    }
+}
+
+/// Stable pseudo random value of the cell, the precipitation must not draw from the game
+/// generators to keep the runs reproducible
+fn cell_hash(x: i32, y: i32) -> u64 {
+    let h = (x as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        ^ (y as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f);
+    (h ^ (h >> 29)).wrapping_mul(0x1656_67b1_9e37_79f9) >> 32
+}
+
+/// Rolls the weather of every new day, floods or dries the shores and logs it
+pub fn process_weather(
+    calendar: Res<Calendar>,
+    mut weather: ResMut<Weather>,
+    mut map: ResMut<MapField>,
+    mut revision: ResMut<MapRevision>,
+    occupied: View<&Point>,
+    mut log: ResMut<MessageLog>,
+) {
+    let day = calendar.days();
+    if day == weather.day {
+        return;
+    }
+    let kind = weather.roll(calendar.season());
+    weather.streak = if kind == weather.kind {
+        weather.streak + 1
+    } else {
+        1
+    };
+    weather.kind = kind;
+    weather.day = day;
+    let date = calendar.to_string();
+    log.post(kind.severity(), format!("{date}: {kind}"));
+
+    let change = kind.shore_change();
+    if change == 0. {
+        return;
+    }
+    // Floods spread over the land next to the water, droughts dry what the floods covered
+    let is_changed = if change > 0. {
+        let occupied: HashSet<Point> = occupied.iter().copied().collect();
+        weather.flood(&mut map, &occupied, change)
+    } else {
+        weather.recede(&mut map, -change)
+    };
+    if is_changed {
+        revision.bump();
+    }
+}
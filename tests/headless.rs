@@ -0,0 +1,70 @@
+use rltk::{Point, VirtualKeyCode};
+use rust_yoeman::{
+    State,
+    ambience::{
+        generator::MapPreset,
+        map::{MapField, is_walkable},
+    },
+    components::Plant,
+    headless::Headless,
+    input::KeyBindings,
+    rng::GameRng,
+    spawn::create_plant_flow,
+};
+
+const SEED: u64 = 7;
+
+fn new_game() -> Headless {
+    let state = State::new(GameRng::new(SEED), MapPreset::Noise, KeyBindings::default());
+    Headless::new(state.expect("Game setup failed"))
+}
+
+fn player_position(headless: &Headless) -> Point {
+    *headless
+        .world()
+        .view::<&Point>()
+        .try_get(headless.state.player_id)
+        .expect("Player does not exist")
+}
+
+#[test]
+fn player_steps_right() {
+    let mut headless = new_game();
+    let start = player_position(&headless);
+    let destination = start + Point::new(1, 0);
+    let is_open = is_walkable(&headless.world().expect_resource::<MapField>(), destination);
+
+    headless.run(1, &[(0, VirtualKeyCode::Right)]);
+    assert_eq!(player_position(&headless), start, "the step takes time");
+
+    headless.run(60, &[]);
+    let expected = if is_open { destination } else { start };
+    assert_eq!(player_position(&headless), expected);
+}
+
+#[test]
+fn plant_grows() {
+    let mut headless = new_game();
+    let pos = player_position(&headless);
+    create_plant_flow(&mut headless.state.world, pos, "rye");
+
+    headless.run(300, &[]);
+    let stage = headless
+        .world()
+        .view::<(&Point, &Plant)>()
+        .iter()
+        .find(|(p, _)| **p == pos)
+        .map(|(_, plant)| plant.stage())
+        .expect("Plant does not exist");
+    assert!(stage > 0, "the plant is still at the first stage");
+}
+
+#[test]
+fn same_seed_same_run() {
+    let script = [(0, VirtualKeyCode::Right), (30, VirtualKeyCode::Down)];
+    let mut first = new_game();
+    first.run(120, &script);
+    let mut second = new_game();
+    second.run(120, &script);
+    assert_eq!(first.summary(), second.summary());
+}